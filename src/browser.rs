@@ -0,0 +1,152 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState};
+use ratatui::Frame;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::centered_rect;
+use crate::theme::Theme;
+
+/// Popup listing the files a load or save operation accepts.
+pub struct FileBrowser {
+    pub title: String,
+    dir: PathBuf,
+    extensions: &'static [&'static str],
+    /// Offers a "new file" entry, for save targets.
+    allow_new: bool,
+    entries: Vec<Entry>,
+    selected: usize,
+}
+
+enum Entry {
+    NewFile,
+    Parent,
+    Dir(PathBuf),
+    File(PathBuf),
+}
+
+pub enum BrowserEvent {
+    Pending,
+    Selected(PathBuf),
+    /// A new file should be named inside this directory.
+    NewFile(PathBuf),
+    Cancel,
+}
+
+impl FileBrowser {
+    pub fn new(
+        title: impl Into<String>,
+        dir: &Path,
+        extensions: &'static [&'static str],
+        allow_new: bool,
+    ) -> Self {
+        let mut browser = FileBrowser {
+            title: title.into(),
+            dir: dir.to_path_buf(),
+            extensions,
+            allow_new,
+            entries: Vec::new(),
+            selected: 0,
+        };
+        browser.refresh();
+        browser
+    }
+
+    fn refresh(&mut self) {
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+
+        if let Ok(read_dir) = fs::read_dir(&self.dir) {
+            for path in read_dir.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| self.extensions.contains(&ext))
+                {
+                    files.push(path);
+                }
+            }
+        }
+        dirs.sort();
+        files.sort();
+
+        self.entries.clear();
+        if self.allow_new {
+            self.entries.push(Entry::NewFile);
+        }
+        self.entries.push(Entry::Parent);
+        self.entries.extend(dirs.into_iter().map(Entry::Dir));
+        self.entries.extend(files.into_iter().map(Entry::File));
+        self.selected = 0;
+    }
+
+    fn enter(&mut self, dir: PathBuf) {
+        self.dir = dir;
+        self.refresh();
+    }
+
+    fn parent(&self) -> PathBuf {
+        // `..` rather than `Path::parent` so relative directories keep working
+        self.dir.join("..")
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> BrowserEvent {
+        match key.code {
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.selected = (self.selected + 1).min(self.entries.len() - 1);
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            KeyCode::Backspace | KeyCode::Char('h') => self.enter(self.parent()),
+            KeyCode::Esc | KeyCode::Char('q') => return BrowserEvent::Cancel,
+            KeyCode::Enter => match &self.entries[self.selected] {
+                Entry::NewFile => return BrowserEvent::NewFile(self.dir.clone()),
+                Entry::Parent => self.enter(self.parent()),
+                Entry::Dir(dir) => self.enter(dir.clone()),
+                Entry::File(path) => return BrowserEvent::Selected(path.clone()),
+            },
+            _ => {}
+        }
+
+        BrowserEvent::Pending
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let popup = centered_rect(60, 60, area);
+        let name = |path: &Path| {
+            path.file_name()
+                .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+        };
+        let items: Vec<ListItem> = self
+            .entries
+            .iter()
+            .map(|entry| {
+                ListItem::new(match entry {
+                    Entry::NewFile => "<new file>".to_string(),
+                    Entry::Parent => "../".to_string(),
+                    Entry::Dir(dir) => format!("{}/", name(dir)),
+                    Entry::File(path) => name(path),
+                })
+            })
+            .collect();
+
+        let title = format!("{} ({})", self.title, self.extensions.join(", "));
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .title(title)
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(theme.popup)),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let mut state = ListState::default().with_selected(Some(self.selected));
+
+        f.render_widget(Clear, popup);
+        f.render_stateful_widget(list, popup, &mut state);
+    }
+}
@@ -0,0 +1,234 @@
+use std::ops::Range;
+use std::path::PathBuf;
+
+use crate::terrain::Falloff;
+
+/// Past this many layers the finest is below a cell even on large maps.
+const MAX_OCTAVES: u32 = 12;
+
+pub const USAGE: &str = "\
+Usage:
+  gamelife [OPTIONS]                        start the editor
+  gamelife --help                           print this message
+  gamelife gen --seeds <RANGE> --out <DIR>  export one thumbnail per seed
+
+Options:
+  --x-tick <N>         cells between X ruler labels
+  --y-tick <N>         cells between Y ruler labels
+  --width <N>          map width in cells [default: 200]
+  --height <N>         map height in cells [default: 200]
+  --start <MAP>        initial map: empty, noise, island or caves
+  --frequency <F>      noise periods across the map [default: 10]
+  --octaves <N>        noise layers, each twice as fine [default: 1]
+  --persistence <P>    amplitude each layer keeps from the last [default: 0.5]
+  --island-mask <F>    fade noise out towards the edges: linear or squared
+  --mask-strength <S>  how far the island mask fades, 0 to 1 [default: 1]
+  --stdin              read the initial map from stdin as text
+  --load <FILE>        open a saved map, at the size it was saved with
+  --dump               print the map as text and exit, without the editor
+  --exec <FILE>        run a file of : commands on the map and exit
+
+Options for gen:
+  --seeds <RANGE>      seed or range of seeds: 7, 1..20 or 1..=20
+  --out <DIR>          directory the thumbnails are written to
+  --format <png|txt>   thumbnail format [default: png]
+  --transpose          write txt thumbnails column-major
+
+Environment:
+  GAMELIVE_LOG         log file [default: gamelive.log]
+  GAMELIVE_LOG_LEVEL   off, error, warn, info, debug or trace [default: debug]";
+
+pub enum Command {
+    Help,
+    Tui(TuiArgs),
+    Gen(GenArgs),
+}
+
+/// Overrides for the editor; `None` keeps the saved preference.
+#[derive(Default)]
+pub struct TuiArgs {
+    pub x_tick: Option<usize>,
+    pub y_tick: Option<usize>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub frequency: Option<f64>,
+    pub octaves: Option<u32>,
+    pub persistence: Option<f64>,
+    pub island_mask: Option<Falloff>,
+    pub island_strength: Option<f64>,
+    /// Checked once the logger is up, so an unknown name can fall back with
+    /// a warning.
+    pub start: Option<String>,
+    /// Read the initial map from stdin instead of generating one.
+    pub stdin: bool,
+    /// Saved map to open instead of generating one.
+    pub load: Option<PathBuf>,
+    /// Print the initial map and exit instead of starting the editor.
+    pub dump: bool,
+    /// Command script to run without the editor; `--dump` then prints the
+    /// result.
+    pub exec: Option<PathBuf>,
+}
+
+pub struct GenArgs {
+    pub seeds: Range<u32>,
+    pub out: PathBuf,
+    pub format: ThumbnailFormat,
+    pub transpose: bool,
+}
+
+#[derive(Clone, Copy)]
+pub enum ThumbnailFormat {
+    Png,
+    Txt,
+}
+
+impl ThumbnailFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ThumbnailFormat::Png => "png",
+            ThumbnailFormat::Txt => "txt",
+        }
+    }
+}
+
+/// Parses everything after the program name.
+pub fn parse_args(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.peekable();
+    match args.peek().map(String::as_str) {
+        Some("-h" | "--help") => Ok(Command::Help),
+        Some("gen") => parse_gen_args(args.skip(1)).map(Command::Gen),
+        _ => parse_tui_args(args).map(Command::Tui),
+    }
+}
+
+fn parse_tui_args(mut args: impl Iterator<Item = String>) -> Result<TuiArgs, String> {
+    let mut tui_args = TuiArgs::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for '{}'", arg));
+        match arg.as_str() {
+            "--x-tick" => tui_args.x_tick = Some(parse_tick(&value()?)?),
+            "--y-tick" => tui_args.y_tick = Some(parse_tick(&value()?)?),
+            "--width" => tui_args.width = Some(parse_dimension(&value()?, "width")?),
+            "--height" => tui_args.height = Some(parse_dimension(&value()?, "height")?),
+            "--frequency" => tui_args.frequency = Some(parse_frequency(&value()?)?),
+            "--octaves" => tui_args.octaves = Some(parse_octaves(&value()?)?),
+            "--persistence" => tui_args.persistence = Some(parse_persistence(&value()?)?),
+            "--island-mask" => {
+                let name = value()?;
+                let falloff = Falloff::parse(&name);
+                let falloff = falloff.ok_or(format!("unknown island falloff '{}'", name))?;
+                tui_args.island_mask = Some(falloff);
+            }
+            "--mask-strength" => {
+                tui_args.island_strength = Some(parse_island_strength(&value()?)?)
+            }
+            "--start" => tui_args.start = Some(value()?),
+            "--stdin" => tui_args.stdin = true,
+            "--load" => tui_args.load = Some(PathBuf::from(value()?)),
+            "--dump" => tui_args.dump = true,
+            "--exec" => tui_args.exec = Some(PathBuf::from(value()?)),
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+
+    Ok(tui_args)
+}
+
+pub fn parse_tick(input: &str) -> Result<usize, String> {
+    match input.parse::<usize>() {
+        Ok(tick) if tick > 0 => Ok(tick),
+        _ => Err(format!("invalid tick interval '{}'", input)),
+    }
+}
+
+fn parse_frequency(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(frequency) if frequency > 0. && frequency.is_finite() => Ok(frequency),
+        _ => Err(format!("invalid frequency '{}'", input)),
+    }
+}
+
+fn parse_octaves(input: &str) -> Result<u32, String> {
+    match input.parse::<u32>() {
+        Ok(octaves) if (1..=MAX_OCTAVES).contains(&octaves) => Ok(octaves),
+        _ => Err(format!("invalid octaves '{}', expected 1 to {}", input, MAX_OCTAVES)),
+    }
+}
+
+fn parse_persistence(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(persistence) if persistence > 0. && persistence <= 1. => Ok(persistence),
+        _ => Err(format!("invalid persistence '{}', expected above 0 up to 1", input)),
+    }
+}
+
+fn parse_island_strength(input: &str) -> Result<f64, String> {
+    match input.parse::<f64>() {
+        Ok(strength) if (0. ..=1.).contains(&strength) => Ok(strength),
+        _ => Err(format!("invalid island strength '{}', expected 0 to 1", input)),
+    }
+}
+
+fn parse_dimension(input: &str, name: &str) -> Result<usize, String> {
+    match input.parse::<usize>() {
+        Ok(cells) if cells > 0 => Ok(cells),
+        _ => Err(format!("invalid map {} '{}', must be at least 1", name, input)),
+    }
+}
+
+fn parse_gen_args(mut args: impl Iterator<Item = String>) -> Result<GenArgs, String> {
+    let mut seeds = None;
+    let mut out = None;
+    let mut format = ThumbnailFormat::Png;
+    let mut transpose = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for '{}'", arg));
+        match arg.as_str() {
+            "--seeds" => seeds = Some(parse_seed_range(&value()?)?),
+            "--out" => out = Some(PathBuf::from(value()?)),
+            "--format" => {
+                format = match value()?.as_str() {
+                    "png" => ThumbnailFormat::Png,
+                    "txt" => ThumbnailFormat::Txt,
+                    other => return Err(format!("unknown format '{}'", other)),
+                }
+            }
+            "--transpose" => transpose = true,
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+
+    Ok(GenArgs {
+        seeds: seeds.ok_or("missing --seeds")?,
+        out: out.ok_or("missing --out")?,
+        format,
+        transpose,
+    })
+}
+
+/// `a..b` is exclusive like a Rust range, `a..=b` inclusive, `a` a single seed.
+fn parse_seed_range(input: &str) -> Result<Range<u32>, String> {
+    let parse = |s: &str| {
+        s.trim()
+            .parse::<u32>()
+            .map_err(|_| format!("invalid seed '{}'", s))
+    };
+
+    let range = if let Some((start, end)) = input.split_once("..=") {
+        parse(start)?..parse(end)?.saturating_add(1)
+    } else if let Some((start, end)) = input.split_once("..") {
+        parse(start)?..parse(end)?
+    } else {
+        let seed = parse(input)?;
+        seed..seed.saturating_add(1)
+    };
+
+    if range.is_empty() {
+        return Err(format!("empty seed range '{}'", input));
+    }
+
+    Ok(range)
+}
@@ -0,0 +1,151 @@
+use std::path::PathBuf;
+
+use crate::cli::parse_tick;
+
+const DEFAULT_DROPLETS: usize = 2000;
+const DEFAULT_ITERATIONS: usize = 10;
+const DEFAULT_RIVERS: usize = 6;
+
+/// A parsed `:` command line.
+pub enum Command {
+    /// `:w`, `:w<slot>` or `:w <path>`; `:save` is the same as `:w`
+    Write(Target),
+    /// `:e`, `:e<slot>` or `:e <path>`
+    Edit(Target),
+    /// `:export` or `:export <path>`, as `.png`, `.pgm` or `.txt`
+    Export(Option<PathBuf>),
+    /// `:import` or `:import <path>`, a `.txt` map as `:export` writes them
+    Import(Option<PathBuf>),
+    /// `:fill`, turns every unlocked cell to land
+    Fill,
+    /// `:transpose`, toggles column-major map files
+    Transpose,
+    /// `:origin`, toggles counting rows from the bottom edge
+    Origin,
+    /// `:smooth`, toggles eased camera movement
+    Smooth,
+    /// `:snap`, toggles page jumps stopping on ruler labels
+    Snap,
+    /// `:edgepan`, toggles scrolling when a drag reaches the map edge
+    EdgePan,
+    /// `:tolerance <value>`, how far the bucket fill spreads
+    Tolerance(f64),
+    /// `:xtick <n>`, cells between X ruler labels
+    XTick(usize),
+    /// `:ytick <n>`, cells between Y ruler labels
+    YTick(usize),
+    /// `:preset <name>`
+    Preset(String),
+    /// `:theme <name>`
+    Theme(String),
+    /// `:regen`, rerolls the noise in view
+    Regen,
+    /// `:seed <n>`, regenerates the whole map from seed `n`
+    Seed(u32),
+    /// `:erode [droplets] [iterations]`, runs rain over the map
+    Erode { droplets: usize, iterations: usize },
+    /// `:rivers [count]`, carves rivers from the highest land to the water
+    Rivers(usize),
+    /// `:diff <path>` compares the map with a saved one, `:diff` stops
+    Diff(Option<PathBuf>),
+    /// `:q`
+    Quit,
+}
+
+/// File a write or edit applies to; `Browse` lets the user pick one.
+pub enum Target {
+    Browse,
+    Slot(u8),
+    Path(PathBuf),
+}
+
+/// The leading `:` may be typed too, as scripts often do.
+pub fn parse_command(input: &str) -> Result<Command, String> {
+    let input = input.trim();
+    let input = input.strip_prefix(':').unwrap_or(input).trim_start();
+    let (name, arg) = match input.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, arg.trim()),
+        None => (input, ""),
+    };
+
+    match name {
+        "w" | "save" => Ok(Command::Write(parse_target(arg))),
+        "e" => Ok(Command::Edit(parse_target(arg))),
+        "export" if arg.is_empty() => Ok(Command::Export(None)),
+        "export" => Ok(Command::Export(Some(PathBuf::from(arg)))),
+        "import" if arg.is_empty() => Ok(Command::Import(None)),
+        "import" => Ok(Command::Import(Some(PathBuf::from(arg)))),
+        "fill" if arg.is_empty() => Ok(Command::Fill),
+        "transpose" if arg.is_empty() => Ok(Command::Transpose),
+        "origin" if arg.is_empty() => Ok(Command::Origin),
+        "smooth" if arg.is_empty() => Ok(Command::Smooth),
+        "snap" if arg.is_empty() => Ok(Command::Snap),
+        "edgepan" if arg.is_empty() => Ok(Command::EdgePan),
+        "tolerance" => match arg.parse::<f64>() {
+            Ok(tolerance) if tolerance >= 0. => Ok(Command::Tolerance(tolerance)),
+            _ => Err(format!("invalid tolerance '{}'", arg)),
+        },
+        "xtick" => parse_tick(arg).map(Command::XTick),
+        "ytick" => parse_tick(arg).map(Command::YTick),
+        "preset" if !arg.is_empty() => Ok(Command::Preset(arg.to_string())),
+        "theme" if !arg.is_empty() => Ok(Command::Theme(arg.to_string())),
+        "regen" if arg.is_empty() => Ok(Command::Regen),
+        "seed" => match arg.parse::<u32>() {
+            Ok(seed) => Ok(Command::Seed(seed)),
+            Err(_) => Err(format!("invalid seed '{}'", arg)),
+        },
+        "erode" => parse_erode(arg),
+        "rivers" if arg.is_empty() => Ok(Command::Rivers(DEFAULT_RIVERS)),
+        "rivers" => match arg.parse::<usize>() {
+            Ok(count) if count > 0 => Ok(Command::Rivers(count)),
+            _ => Err(format!("invalid river count '{}'", arg)),
+        },
+        "diff" if arg.is_empty() => Ok(Command::Diff(None)),
+        "diff" => Ok(Command::Diff(Some(PathBuf::from(arg)))),
+        "q" if arg.is_empty() => Ok(Command::Quit),
+        "" => Err("empty command".to_string()),
+        _ => {
+            if let Some(slot) = name.strip_prefix('w').and_then(parse_slot) {
+                return Ok(Command::Write(Target::Slot(slot?)));
+            }
+            if let Some(slot) = name.strip_prefix('e').and_then(parse_slot) {
+                return Ok(Command::Edit(Target::Slot(slot?)));
+            }
+            Err(format!("unknown command '{}'", input))
+        }
+    }
+}
+
+/// Either count may be left out, taking the default from there on.
+fn parse_erode(arg: &str) -> Result<Command, String> {
+    let mut counts = arg.split_whitespace().map(|count| match count.parse::<usize>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("invalid count '{}'", count)),
+    });
+    let droplets = counts.next().unwrap_or(Ok(DEFAULT_DROPLETS))?;
+    let iterations = counts.next().unwrap_or(Ok(DEFAULT_ITERATIONS))?;
+    if counts.next().is_some() {
+        return Err("expected :erode [droplets] [iterations]".to_string());
+    }
+    Ok(Command::Erode {
+        droplets,
+        iterations,
+    })
+}
+
+fn parse_target(arg: &str) -> Target {
+    if arg.is_empty() {
+        Target::Browse
+    } else {
+        Target::Path(PathBuf::from(arg))
+    }
+}
+
+/// `None` when `input` isn't a number at all, so it can be another command.
+fn parse_slot(input: &str) -> Option<Result<u8, String>> {
+    let slot = input.parse::<u32>().ok()?;
+    Some(match slot {
+        1..=9 => Ok(slot as u8),
+        _ => Err(format!("expected a slot from 1 to 9, got '{}'", input)),
+    })
+}
@@ -0,0 +1,273 @@
+use ratatui::layout::Rect;
+
+use crate::prefs::Prefs;
+use crate::{MapSize, RULLER_DOWN_SIZE, RULLER_LEFT_SIZE, RULLER_RIGHT_SIZE, RULLER_UP_SIZE};
+
+/// Where the map is drawn on screen and which part of it is visible. All
+/// conversions between screen and map coordinates go through here.
+#[derive(Clone, Copy)]
+pub struct Viewport {
+    /// Screen area showing map cells, rulers excluded.
+    pub area: Rect,
+    pub camera_x: usize,
+    pub camera_y: usize,
+    /// Map cells per screen column.
+    pub col_scale: usize,
+    /// Map cells per screen row.
+    pub row_scale: usize,
+    pub size: MapSize,
+    /// Rows are shown counted up from the bottom edge.
+    origin_bottom: bool,
+}
+
+impl Viewport {
+    /// Lays the map out in `area` (rulers included), clamping the camera so
+    /// it never scrolls past the map edges.
+    pub fn new(
+        area: Rect,
+        camera_x: usize,
+        camera_y: usize,
+        size: MapSize,
+        prefs: &Prefs,
+    ) -> Self {
+        let mut viewport = Viewport {
+            area: map_area(area, size, prefs),
+            camera_x,
+            camera_y,
+            col_scale: prefs.zoom,
+            row_scale: row_scale(prefs),
+            size,
+            origin_bottom: prefs.origin_bottom,
+        };
+        let (max_x, max_y) = viewport.max_camera();
+        viewport.camera_x = camera_x.min(max_x);
+        viewport.camera_y = camera_y.min(max_y);
+
+        viewport
+    }
+
+    /// Visible width in map cells.
+    pub fn map_width(&self) -> usize {
+        self.area.width as usize * self.col_scale
+    }
+
+    /// Visible height in map cells.
+    pub fn map_height(&self) -> usize {
+        self.area.height as usize * self.row_scale
+    }
+
+    /// Largest camera position that still fills the screen.
+    pub fn max_camera(&self) -> (usize, usize) {
+        (
+            self.size.width.saturating_sub(self.map_width()),
+            self.size.height.saturating_sub(self.map_height()),
+        )
+    }
+
+    /// Map cell under a screen position, `None` outside of the map area or
+    /// past the map edge. When zoomed out this is the top-left cell of the
+    /// block under the position.
+    pub fn screen_to_map(&self, col: u16, row: u16) -> Option<(usize, usize)> {
+        let area = self.area;
+        if col < area.x || col >= area.right() || row < area.y || row >= area.bottom() {
+            return None;
+        }
+
+        let x = (col - area.x) as usize * self.col_scale + self.camera_x;
+        let y = (row - area.y) as usize * self.row_scale + self.camera_y;
+        self.size.contains(x, y).then_some((x, y))
+    }
+
+    /// Screen position showing a map cell, `None` when it is scrolled out of
+    /// view or past the map edge.
+    pub fn map_to_screen(&self, x: usize, y: usize) -> Option<(u16, u16)> {
+        if !self.size.contains(x, y) || x < self.camera_x || y < self.camera_y {
+            return None;
+        }
+
+        let col = (x - self.camera_x) / self.col_scale;
+        let row = (y - self.camera_y) / self.row_scale;
+        if col < self.area.width as usize && row < self.area.height as usize {
+            Some((self.area.x + col as u16, self.area.y + row as u16))
+        } else {
+            None
+        }
+    }
+
+    /// Row number shown for map row `y`. It is its own inverse, so it also
+    /// turns a row typed by the user back into a map row.
+    pub fn display_y(&self, y: usize) -> usize {
+        if self.origin_bottom {
+            self.size.height - 1 - y
+        } else {
+            y
+        }
+    }
+}
+
+/// Columns left and right of, and rows above and below the map taken by
+/// rulers.
+fn ruler_margins(prefs: &Prefs) -> (u16, u16, u16, u16) {
+    // Each ruler only takes space while it is drawn
+    let margin = |shown: bool, size: usize| if shown { size as u16 } else { 0 };
+    (
+        margin(prefs.show_y_ruler, RULLER_LEFT_SIZE),
+        margin(prefs.show_right_ruler, RULLER_RIGHT_SIZE),
+        margin(prefs.show_x_ruler, RULLER_UP_SIZE),
+        margin(prefs.show_bottom_ruler, RULLER_DOWN_SIZE),
+    )
+}
+
+/// Part of `area` showing map cells: everything but the rulers, shrunk and
+/// centered when the whole map fits at the current zoom.
+fn map_area(area: Rect, size: MapSize, prefs: &Prefs) -> Rect {
+    let (left, right, top, bottom) = ruler_margins(prefs);
+    let avail_width = area.width.saturating_sub(left + right);
+    let avail_height = area.height.saturating_sub(top + bottom);
+
+    let width = (avail_width as usize).min(size.width.div_ceil(prefs.zoom)) as u16;
+    let height = (avail_height as usize).min(size.height.div_ceil(row_scale(prefs))) as u16;
+
+    Rect {
+        x: area.x + left.min(area.width) + (avail_width - width) / 2,
+        y: area.y + top.min(area.height) + (avail_height - height) / 2,
+        width,
+        height,
+    }
+}
+
+/// The map area grown back by its rulers.
+pub fn ruled_area(view: Rect, prefs: &Prefs) -> Rect {
+    let (left, right, top, bottom) = ruler_margins(prefs);
+
+    Rect {
+        x: view.x.saturating_sub(left),
+        y: view.y.saturating_sub(top),
+        width: view.width + left + right,
+        height: view.height + top + bottom,
+    }
+}
+
+/// Map cells covered by one screen row; half blocks pack two rows of cells.
+fn row_scale(prefs: &Prefs) -> usize {
+    if prefs.half_block {
+        prefs.zoom * 2
+    } else {
+        prefs.zoom
+    }
+}
+
+/// Smallest zoom showing the whole map inside `area`.
+pub fn fit_zoom(area: Rect, size: MapSize, prefs: &Prefs) -> usize {
+    let (left, right, top, bottom) = ruler_margins(prefs);
+    let avail_width = area.width.saturating_sub(left + right).max(1) as usize;
+    let mut avail_height = area.height.saturating_sub(top + bottom).max(1) as usize;
+    if prefs.half_block {
+        avail_height *= 2;
+    }
+
+    size.width
+        .div_ceil(avail_width)
+        .max(size.height.div_ceil(avail_height))
+        .max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::layout::Position;
+
+    const SCREEN: Rect = Rect {
+        x: 2,
+        y: 1,
+        width: 60,
+        height: 20,
+    };
+
+    /// Every mix of the four rulers, at zoom 1 and 3, full and half block.
+    fn layouts() -> Vec<Prefs> {
+        let mut layouts = Vec::new();
+        for rulers in 0..16 {
+            for (zoom, half_block) in [(1, false), (3, false), (1, true)] {
+                layouts.push(Prefs {
+                    show_x_ruler: rulers & 1 != 0,
+                    show_y_ruler: rulers & 2 != 0,
+                    show_bottom_ruler: rulers & 4 != 0,
+                    show_right_ruler: rulers & 8 != 0,
+                    zoom,
+                    half_block,
+                    ..Prefs::default()
+                });
+            }
+        }
+        layouts
+    }
+
+    #[test]
+    fn screen_to_map_undoes_map_to_screen() {
+        let size = MapSize {
+            width: 200,
+            height: 120,
+        };
+        for prefs in layouts() {
+            for (camera_x, camera_y) in [(0, 0), (37, 12), (500, 500)] {
+                let viewport = Viewport::new(SCREEN, camera_x, camera_y, size, &prefs);
+                let area = viewport.area;
+                for row in area.y..area.bottom() {
+                    for col in area.x..area.right() {
+                        let (x, y) = viewport.screen_to_map(col, row).unwrap();
+                        assert_eq!(viewport.map_to_screen(x, y), Some((col, row)));
+                        // Every cell of the block shows at the same place
+                        let (last_x, last_y) =
+                            (x + viewport.col_scale - 1, y + viewport.row_scale - 1);
+                        assert_eq!(viewport.map_to_screen(last_x, last_y), Some((col, row)));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn map_to_screen_undoes_screen_to_map() {
+        // Small enough to fit on screen, so the map area is centered
+        let size = MapSize {
+            width: 30,
+            height: 9,
+        };
+        for prefs in layouts() {
+            for (camera_x, camera_y) in [(0, 0), (4, 2)] {
+                let viewport = Viewport::new(SCREEN, camera_x, camera_y, size, &prefs);
+                for y in 0..size.height {
+                    for x in 0..size.width {
+                        let Some((col, row)) = viewport.map_to_screen(x, y) else {
+                            continue;
+                        };
+                        let block = (
+                            x - (x - viewport.camera_x) % viewport.col_scale,
+                            y - (y - viewport.camera_y) % viewport.row_scale,
+                        );
+                        assert_eq!(viewport.screen_to_map(col, row), Some(block));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rulers_are_off_the_map() {
+        let size = MapSize {
+            width: 200,
+            height: 120,
+        };
+        for prefs in layouts() {
+            let viewport = Viewport::new(SCREEN, 0, 0, size, &prefs);
+            let ruled = ruled_area(viewport.area, &prefs);
+            for row in ruled.y..ruled.bottom() {
+                for col in ruled.x..ruled.right() {
+                    let inside = viewport.area.contains(Position { x: col, y: row });
+                    assert_eq!(viewport.screen_to_map(col, row).is_some(), inside);
+                }
+            }
+        }
+    }
+}
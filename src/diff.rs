@@ -0,0 +1,41 @@
+use std::cmp::Ordering;
+
+/// How each cell of `a` compares with the same cell of `b`: `Greater` where
+/// `a` is higher. Missing cells count as lower than any value, so for drawn
+/// maps `Greater` is land added in `a` and `Less` land taken away.
+pub fn diff_maps(a: &[Vec<f64>], b: &[Vec<f64>]) -> Vec<Vec<Ordering>> {
+    a.iter()
+        .zip(b)
+        .map(|(row_a, row_b)| {
+            row_a
+                .iter()
+                .zip(row_b)
+                .map(|(&a, &b)| match (a.is_nan(), b.is_nan()) {
+                    (true, true) => Ordering::Equal,
+                    (true, false) => Ordering::Less,
+                    (false, true) => Ordering::Greater,
+                    (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// First change in the `zoom`×`zoom` block at `(x, y)`, so zoomed out views
+/// still show single changed cells.
+pub fn block_diff(diff: &[Vec<Ordering>], x: usize, y: usize, zoom: usize) -> Ordering {
+    diff[y..(y + zoom).min(diff.len())]
+        .iter()
+        .flat_map(|row| &row[x..(x + zoom).min(row.len())])
+        .copied()
+        .find(|&ordering| ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Cells higher and lower than in the comparison.
+pub fn count_changes(diff: &[Vec<Ordering>]) -> (usize, usize) {
+    let changes = diff.iter().flatten();
+    let higher = changes.clone().filter(|&&o| o == Ordering::Greater).count();
+    let lower = changes.filter(|&&o| o == Ordering::Less).count();
+    (higher, lower)
+}
@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+
+use crate::command::Target;
+use crate::save;
+use crate::Locks;
+
+/// Written by Ctrl+s and read by Ctrl+o before the map has a file.
+const DEFAULT_FILE: &str = "map.gml";
+
+/// The file the map was last loaded from or written to, and what it held,
+/// so unsaved changes can be told apart.
+pub struct Document {
+    pub path: Option<PathBuf>,
+    /// Slot used last, kept when a plain path is written or loaded after it.
+    pub slot: Option<u8>,
+    saved_cells: Vec<Vec<f64>>,
+    saved_locks: Locks,
+}
+
+impl Document {
+    pub fn new(path: Option<PathBuf>, map: &[Vec<f64>], locked: &Locks) -> Self {
+        Document {
+            path,
+            slot: None,
+            saved_cells: map.to_vec(),
+            saved_locks: locked.clone(),
+        }
+    }
+
+    /// Records that `path` now holds exactly `map` and `locked`.
+    pub fn saved(&mut self, path: &Path, slot: Option<u8>, map: &[Vec<f64>], locked: &Locks) {
+        self.path = Some(path.to_path_buf());
+        self.slot = slot.or(self.slot);
+        self.saved_cells = map.to_vec();
+        self.saved_locks.clone_from(locked);
+    }
+
+    /// Compared bit for bit, so holes in loaded maps still count as equal.
+    pub fn is_modified(&self, map: &[Vec<f64>], locked: &Locks) -> bool {
+        let mut cells = map.iter().flatten().zip(self.saved_cells.iter().flatten());
+        cells.any(|(a, b)| a.to_bits() != b.to_bits()) || *locked != self.saved_locks
+    }
+
+    /// Where Ctrl+s and Ctrl+o go: the current file, as its slot if it is
+    /// one so it stays row-major.
+    pub fn target(&self) -> Target {
+        let path = self.path.clone().unwrap_or_else(|| PathBuf::from(DEFAULT_FILE));
+        match self.slot {
+            Some(slot) if save::slot_path(slot) == path => Target::Slot(slot),
+            _ => Target::Path(path),
+        }
+    }
+
+    /// File name, starred while modified, then the program name.
+    pub fn title(&self, map: &[Vec<f64>], locked: &Locks) -> String {
+        let name = self
+            .path
+            .as_deref()
+            .and_then(Path::file_name)
+            .map_or("untitled".into(), |name| name.to_string_lossy());
+        let star = if self.is_modified(map, locked) { "*" } else { "" };
+        format!("{}{} - gamelife", name, star)
+    }
+}
@@ -0,0 +1,145 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::save;
+use crate::{block_char, Glyphs, MapSize, EMPTY, FILLED, MISSING, OUT_OF_RANGE, WALL};
+
+/// Writes the cells of `region`, as `(x0, y0, x1, y1)`, as text, one row per
+/// line, without rulers. With `transpose` each line is a column instead.
+pub fn export_ascii(
+    map: &[Vec<f64>],
+    region: (usize, usize, usize, usize),
+    path: &Path,
+    transpose: bool,
+    water_level: f64,
+) -> io::Result<()> {
+    fs::write(path, ascii_text(map, region, transpose, water_level))
+}
+
+/// The text `export_ascii` writes. The region is clipped to the map.
+pub fn ascii_text(
+    map: &[Vec<f64>],
+    region: (usize, usize, usize, usize),
+    transpose: bool,
+    water_level: f64,
+) -> String {
+    let transposed;
+    let (map, (x0, y0, x1, y1)) = if transpose {
+        transposed = save::transpose(map);
+        let (x0, y0, x1, y1) = region;
+        (&transposed[..], (y0, x0, y1, x1))
+    } else {
+        (map, region)
+    };
+    let size = MapSize::of(map);
+
+    let glyph = |x, y| block_char(map, x, y, 1, water_level, Glyphs::Plain);
+    let mut text = String::new();
+    for y in y0..y1.min(size.height) {
+        text.extend((x0..x1.min(size.width)).map(|x| glyph(x, y)));
+        text.push('\n');
+    }
+    text
+}
+
+/// Reads text in the `export_ascii` format back into a map. `#` and `.` are
+/// accepted for land and water, and so are the textured glyph variants.
+/// Text is padded with water or cut off to fit `size`, and characters that
+/// are not cells read as water; both are logged.
+pub fn import_ascii(text: &str, size: MapSize) -> Vec<Vec<f64>> {
+    let mut map = size.grid(0.);
+    let mut cut = false;
+    let mut unknown = Vec::new();
+    for (y, line) in text.lines().enumerate() {
+        if y == size.height {
+            cut = true;
+            break;
+        }
+        for (x, ch) in line.chars().enumerate() {
+            if x == size.width {
+                cut = true;
+                break;
+            }
+            map[y][x] = match ch {
+                FILLED | '▓' | '#' => 1.,
+                EMPTY | '~' | '.' | ' ' => 0.,
+                MISSING => f64::NAN,
+                OUT_OF_RANGE => f64::INFINITY,
+                '─' | '│' | '┌' | '┐' | '└' | '┘' => WALL,
+                '├' | '┤' | '┬' | '┴' | '┼' | '■' => WALL,
+                _ => {
+                    unknown.push((ch, y + 1));
+                    0.
+                }
+            };
+        }
+    }
+
+    if cut {
+        log::warn!("text past {}x{} cells was cut off", size.width, size.height);
+    }
+    if let Some((ch, line)) = unknown.first() {
+        log::warn!(
+            "{} unknown cells read as water, the first '{}' at line {}",
+            unknown.len(),
+            ch,
+            line
+        );
+    }
+    map
+}
+
+/// Reads a text map from `path` as `import_ascii` does.
+pub fn import_ascii_file(path: &Path, size: MapSize) -> io::Result<Vec<Vec<f64>>> {
+    Ok(import_ascii(&fs::read_to_string(path)?, size))
+}
+
+/// Writes the map as an 8-bit grayscale PNG, normalized from its min/max range.
+pub fn export_png(map: &[Vec<f64>], path: &Path) -> io::Result<()> {
+    let height = map.len();
+    let width = map.first().map_or(0, Vec::len);
+
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        width as u32,
+        height as u32,
+    );
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer
+        .write_image_data(&to_grayscale(map))
+        .map_err(io::Error::other)
+}
+
+/// Writes the map as a binary (P5) PGM, normalized like `export_png`.
+pub fn export_pgm(map: &[Vec<f64>], path: &Path) -> io::Result<()> {
+    let size = MapSize::of(map);
+    let mut file = BufWriter::new(File::create(path)?);
+    write!(file, "P5\n{} {}\n255\n", size.width, size.height)?;
+    file.write_all(&to_grayscale(map))?;
+    file.flush()
+}
+
+fn to_grayscale(map: &[Vec<f64>]) -> Vec<u8> {
+    let (min, max) = map
+        .iter()
+        .flatten()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &value| {
+            (min.min(value), max.max(value))
+        });
+    let range = max - min;
+
+    map.iter()
+        .flatten()
+        .map(|&value| {
+            if range > 0. {
+                ((value - min) / range * 255.).round() as u8
+            } else {
+                0
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,145 @@
+use std::collections::VecDeque;
+
+use crate::{Locks, MapSize};
+
+/// Sets the region connected to `start` (through edges, not corners) whose
+/// values lie within `tolerance` of the value at `start` to `new_value`.
+/// With a tolerance of 0 only exactly equal cells spread the fill; NaN cells
+/// only ever match each other. Locked cells stop the fill like a
+/// non-matching value. Refilling a region with the value it already holds
+/// is a no-op. Returns how many cells were filled.
+pub fn flood_fill_tolerance(
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    start: (usize, usize),
+    new_value: f64,
+    tolerance: f64,
+) -> usize {
+    let (start_x, start_y) = start;
+    let size = MapSize::of(map);
+    if !size.contains(start_x, start_y) || locked[start_y][start_x] {
+        return 0;
+    }
+
+    let seed = map[start_y][start_x];
+    if tolerance == 0. && seed.to_bits() == new_value.to_bits() {
+        return 0;
+    }
+    let matches = |value: f64| {
+        if seed.is_nan() {
+            value.is_nan()
+        } else {
+            (value - seed).abs() <= tolerance
+        }
+    };
+
+    // Matching is against the original values, so track visited cells
+    // instead of relying on the new value to stop the spread
+    let mut visited = size.grid(false);
+    let mut queue = VecDeque::from([start]);
+    visited[start_y][start_x] = true;
+    let mut filled = 0;
+
+    while let Some((x, y)) = queue.pop_front() {
+        map[y][x] = new_value;
+        filled += 1;
+
+        let neighbours = [
+            (x.checked_sub(1), Some(y)),
+            (Some(x + 1), Some(y)),
+            (Some(x), y.checked_sub(1)),
+            (Some(x), Some(y + 1)),
+        ];
+        for (nx, ny) in neighbours {
+            let (Some(nx), Some(ny)) = (nx, ny) else {
+                continue;
+            };
+            if size.contains(nx, ny)
+                && !visited[ny][nx]
+                && !locked[ny][nx]
+                && matches(map[ny][nx])
+            {
+                visited[ny][nx] = true;
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+
+    filled
+}
+
+/// Sets the cells matching `inside` that touch a non-matching cell or the map
+/// edge (through an edge, not a corner) to `outline_value`, leaving the
+/// interior alone. `inside` gets each cell's position and value. Locked
+/// cells still shape the outline but keep their value. Returns how many cells
+/// were outlined.
+pub fn outline_region(
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    inside: impl Fn(usize, usize, f64) -> bool,
+    outline_value: f64,
+) -> usize {
+    let size = MapSize::of(map);
+    let is_inside = |map: &[Vec<f64>], x: Option<usize>, y: Option<usize>| match (x, y) {
+        (Some(x), Some(y)) if size.contains(x, y) => inside(x, y, map[y][x]),
+        _ => false,
+    };
+
+    // Find the whole boundary before writing, so outlined cells don't change
+    // what counts as inside
+    let mut boundary = Vec::new();
+    for y in 0..size.height {
+        for x in 0..size.width {
+            if !is_inside(map, Some(x), Some(y)) {
+                continue;
+            }
+            let edge = !is_inside(map, x.checked_sub(1), Some(y))
+                || !is_inside(map, Some(x + 1), Some(y))
+                || !is_inside(map, Some(x), y.checked_sub(1))
+                || !is_inside(map, Some(x), Some(y + 1));
+            if edge {
+                boundary.push((x, y));
+            }
+        }
+    }
+
+    boundary.retain(|&(x, y)| !locked[y][x]);
+    for &(x, y) in &boundary {
+        map[y][x] = outline_value;
+    }
+    boundary.len()
+}
+
+/// Sets the box with corners `from` and `to`, given in either order, to
+/// `value`: every cell when `filled`, otherwise only its border. Corners past
+/// the map are clamped to its edge and locked cells keep their value. Returns
+/// how many cells were written.
+pub fn draw_rect(
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    from: (usize, usize),
+    to: (usize, usize),
+    value: f64,
+    filled: bool,
+) -> usize {
+    let size = MapSize::of(map);
+    if size.width == 0 || size.height == 0 {
+        return 0;
+    }
+    let clamp = |(x, y): (usize, usize)| (x.min(size.width - 1), y.min(size.height - 1));
+    let ((from_x, from_y), (to_x, to_y)) = (clamp(from), clamp(to));
+    let (left, right) = (from_x.min(to_x), from_x.max(to_x));
+    let (top, bottom) = (from_y.min(to_y), from_y.max(to_y));
+
+    let mut written = 0;
+    for y in top..=bottom {
+        for x in left..=right {
+            let border = x == left || x == right || y == top || y == bottom;
+            if (filled || border) && !locked[y][x] {
+                map[y][x] = value;
+                written += 1;
+            }
+        }
+    }
+    written
+}
@@ -0,0 +1,100 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::prefs;
+
+const HISTORY_FILE: &str = "history";
+/// Oldest entries are dropped past this many.
+const MAX_ENTRIES: usize = 100;
+
+/// Past `:` command lines, oldest first, browsed with the arrow keys.
+#[derive(Default)]
+pub struct History {
+    entries: Vec<String>,
+    /// Entry being shown, `None` while editing a fresh line.
+    position: Option<usize>,
+    /// The fresh line, kept while browsing so Down can return to it.
+    draft: String,
+}
+
+impl History {
+    /// Records a submitted line and stops browsing. Blank lines and repeats of
+    /// the latest entry are not stored.
+    pub fn push(&mut self, line: &str) {
+        self.position = None;
+        let line = line.trim();
+        if line.is_empty() || self.entries.last().is_some_and(|last| last == line) {
+            return;
+        }
+        self.entries.push(line.to_string());
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+
+    /// Stops browsing without recording anything.
+    pub fn reset(&mut self) {
+        self.position = None;
+    }
+
+    /// The entry before the one shown; `input` is what is typed right now.
+    /// Stays on the oldest entry once there.
+    pub fn previous(&mut self, input: &str) -> Option<&str> {
+        let position = match self.position {
+            None if self.entries.is_empty() => return None,
+            None => {
+                self.draft = input.to_string();
+                self.entries.len() - 1
+            }
+            Some(position) => position.saturating_sub(1),
+        };
+        self.position = Some(position);
+        Some(&self.entries[position])
+    }
+
+    /// The entry after the one shown, or the line that was being typed when
+    /// browsing started.
+    pub fn next(&mut self) -> Option<&str> {
+        let position = self.position?;
+        if position + 1 < self.entries.len() {
+            self.position = Some(position + 1);
+            Some(&self.entries[position + 1])
+        } else {
+            self.position = None;
+            Some(&self.draft)
+        }
+    }
+}
+
+pub fn history_path() -> PathBuf {
+    prefs::config_dir().join(HISTORY_FILE)
+}
+
+/// One command per line. A missing file is an empty history.
+pub fn load_history(path: &Path) -> History {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) => {
+            if err.kind() != io::ErrorKind::NotFound {
+                log::warn!("failed to read history {}: {}", path.display(), err);
+            }
+            return History::default();
+        }
+    };
+
+    let mut history = History::default();
+    for line in content.lines() {
+        history.push(line);
+    }
+    history
+}
+
+pub fn save_history(history: &History, path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut content = history.entries.join("\n");
+    content.push('\n');
+    fs::write(path, content)
+}
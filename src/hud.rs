@@ -0,0 +1,100 @@
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::Frame;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::theme::Theme;
+
+/// Frames the average frame time is taken over.
+const WINDOW: usize = 60;
+
+/// The system allocator, counting allocations so the HUD can show how many a
+/// frame made.
+pub struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+/// Allocations made since startup.
+pub fn allocations() -> usize {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+/// Render statistics shown in the performance overlay.
+#[derive(Default)]
+pub struct Hud {
+    frame_times: VecDeque<Duration>,
+    redraws: u64,
+    last_allocations: usize,
+}
+
+impl Hud {
+    /// Records one `terminal.draw` call.
+    pub fn record(&mut self, frame_time: Duration, allocations: usize) {
+        if self.frame_times.len() == WINDOW {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+        self.redraws += 1;
+        self.last_allocations = allocations;
+    }
+
+    fn average(&self) -> Duration {
+        let total: Duration = self.frame_times.iter().sum();
+        total / self.frame_times.len().max(1) as u32
+    }
+
+    /// Drawn in the top-right corner of `area`; `cells` is the number of map
+    /// cells on screen.
+    pub fn render(&self, f: &mut Frame, area: Rect, cells: usize, theme: &Theme) {
+        let allocations = match self.last_allocations {
+            0 => "none".to_string(),
+            count => count.to_string(),
+        };
+        let lines = vec![
+            Line::from(format!("frame  {:>8.2?}", self.average())),
+            Line::from(format!("draws  {:>8}", self.redraws)),
+            Line::from(format!("cells  {:>8}", cells)),
+            Line::from(format!("allocs {:>8}", allocations)),
+        ];
+
+        let width = 17.min(area.width);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let popup = Rect {
+            x: area.right() - width,
+            y: area.y,
+            width,
+            height,
+        };
+
+        let paragraph = Paragraph::new(lines).block(
+            Block::default()
+                .title("HUD")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.hud)),
+        );
+
+        f.render_widget(Clear, popup);
+        f.render_widget(paragraph, popup);
+    }
+}
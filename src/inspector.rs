@@ -0,0 +1,70 @@
+use ratatui::layout::Rect;
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::Frame;
+
+use crate::coords::Viewport;
+use crate::theme::Theme;
+use crate::{is_transparent, MapSize};
+
+/// Characters per value, separator included.
+const CELL_WIDTH: u16 = 7;
+
+/// Raw values of the 3×3 cells around `(x, y)`, drawn in the bottom-right
+/// corner of `area`.
+pub fn render(
+    f: &mut Frame,
+    area: Rect,
+    map: &[Vec<f64>],
+    (x, y): (usize, usize),
+    viewport: &Viewport,
+    theme: &Theme,
+) {
+    let width = (CELL_WIDTH * 3 + 2).min(area.width);
+    let height = 5.min(area.height);
+    let popup = Rect {
+        x: area.right() - width,
+        y: area.bottom() - height,
+        width,
+        height,
+    };
+
+    let lines: Vec<Line> = (-1..=1)
+        .map(|dy| {
+            let spans: Vec<Span> = (-1..=1)
+                .map(|dx| {
+                    let text = match neighbour(x, y, dx, dy, viewport.size) {
+                        Some((nx, ny)) if is_transparent(map[ny][nx]) => {
+                            format!("{:>6} ", "·")
+                        }
+                        Some((nx, ny)) => format!("{:>6.2} ", map[ny][nx]),
+                        None => format!("{:>6} ", "-"),
+                    };
+                    if (dx, dy) == (0, 0) {
+                        Span::styled(text, Style::default().add_modifier(Modifier::BOLD))
+                    } else {
+                        Span::raw(text)
+                    }
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .title(format!("{}, {}", x, viewport.display_y(y)))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.inspector)),
+    );
+
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+fn neighbour(x: usize, y: usize, dx: isize, dy: isize, size: MapSize) -> Option<(usize, usize)> {
+    let nx = x.checked_add_signed(dx)?;
+    let ny = y.checked_add_signed(dy)?;
+    size.contains(nx, ny).then_some((nx, ny))
+}
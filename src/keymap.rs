@@ -0,0 +1,301 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::save;
+
+/// Something a key does outside of the prompts, pickers and selections.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    /// Stops the process as Ctrl+Z would in a shell; raw mode keeps that
+    /// chord from reaching the terminal, and undo has it anyway.
+    Suspend,
+    Select,
+    ToggleCursor,
+    DrawAtCursor,
+    EraseAtCursor,
+    Command,
+    Save,
+    Open,
+    ExportText,
+    ExportView,
+    ExportPgm,
+    Undo,
+    Redo,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    HalfPageDown,
+    HalfPageUp,
+    HalfPageLeft,
+    HalfPageRight,
+    PageDown,
+    PageUp,
+    LeftEdge,
+    RightEdge,
+    /// Takes effect on the second press in a row.
+    Top,
+    Bottom,
+    TopLeft,
+    BottomRight,
+    GoTo,
+    Reseed,
+    CenterOnLand,
+    ZoomIn,
+    ZoomOut,
+    Fit,
+    ToggleRulers,
+    ToggleBorderRulers,
+    ToggleXRuler,
+    ToggleYRuler,
+    ToggleBottomRuler,
+    ToggleRightRuler,
+    ToggleHalfBlock,
+    ToggleTextured,
+    ToggleHeatmap,
+    ToggleElevation,
+    ToggleGrid,
+    ToggleHud,
+    ToggleHelp,
+    ToggleInspector,
+    ToggleMinimap,
+    ToggleStats,
+    /// Takes effect on the second press in a row.
+    ResetView,
+    SwapButtons,
+    ShrinkBrush,
+    GrowBrush,
+    SetBrushSize,
+    ToggleWalls,
+    ToggleSmoothBrush,
+    CycleMask,
+    BucketFill,
+    ClearMap,
+    FillMap,
+    ToggleLasso,
+    ToggleLineTool,
+    ToggleRectTool,
+    ToggleLock,
+    TogglePin,
+    NextPin,
+    PreviousPin,
+    CycleStamp,
+    SaveStamp,
+    Annotate,
+    RemoveNote,
+    TogglePause,
+    NextPreset,
+    RaiseSeaLevel,
+    LowerSeaLevel,
+    SetSeaLevel,
+}
+
+/// A key with the modifiers held for it. Shift is folded into the character,
+/// so `N` and `shift+n` are the same combo.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct KeyCombo {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyCombo {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        match code {
+            KeyCode::Char(ch) if modifiers.contains(KeyModifiers::SHIFT) => KeyCombo {
+                code: KeyCode::Char(ch.to_ascii_uppercase()),
+                modifiers: modifiers - KeyModifiers::SHIFT,
+            },
+            _ => KeyCombo { code, modifiers },
+        }
+    }
+
+    /// Reads names like `q`, `ctrl+d`, `alt+x`, `F1`, `PageDown` or `space`.
+    pub fn parse(text: &str) -> Option<KeyCombo> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = text;
+        // A trailing `+` is the key itself, as in `ctrl++`
+        while let Some((modifier, key)) = rest.split_once('+') {
+            if key.is_empty() {
+                break;
+            }
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => return None,
+            };
+            rest = key;
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(ch), None) => KeyCode::Char(ch),
+            _ => match rest.to_ascii_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backspace" => KeyCode::Backspace,
+                name => KeyCode::F(name.strip_prefix('f')?.parse().ok()?),
+            },
+        };
+        Some(KeyCombo::new(code, modifiers))
+    }
+}
+
+/// One line of a key file.
+#[derive(Deserialize)]
+struct Binding {
+    key: String,
+    action: Action,
+}
+
+/// Bindings are tried in order and the first match wins. A key may appear
+/// with several actions, an action with several keys.
+pub type Keymap = Vec<(KeyCombo, Action)>;
+
+/// The keys the editor always had.
+pub fn default_keymap() -> Keymap {
+    use KeyCode::*;
+    let key = |code| KeyCombo::new(code, KeyModifiers::NONE);
+    let ctrl = |code| KeyCombo::new(code, KeyModifiers::CONTROL);
+    let alt = |code| KeyCombo::new(code, KeyModifiers::ALT);
+
+    vec![
+        (key(Char('q')), Action::Quit),
+        (alt(Char('z')), Action::Suspend),
+        (key(Char('V')), Action::Select),
+        (key(Char('t')), Action::ToggleCursor),
+        (key(Enter), Action::DrawAtCursor),
+        (key(Backspace), Action::EraseAtCursor),
+        (key(Char(':')), Action::Command),
+        (ctrl(Char('s')), Action::Save),
+        (ctrl(Char('o')), Action::Open),
+        (key(Char('e')), Action::ExportText),
+        (key(Char('E')), Action::ExportView),
+        (ctrl(Char('e')), Action::ExportPgm),
+        (ctrl(Char('z')), Action::Undo),
+        (ctrl(Char('y')), Action::Redo),
+        (ctrl(Char('Z')), Action::Redo),
+        (key(Char('h')), Action::MoveLeft),
+        (key(Left), Action::MoveLeft),
+        (key(Char('l')), Action::MoveRight),
+        (key(Right), Action::MoveRight),
+        (key(Char('k')), Action::MoveUp),
+        (key(Up), Action::MoveUp),
+        (key(Char('j')), Action::MoveDown),
+        (key(Down), Action::MoveDown),
+        (ctrl(Char('d')), Action::HalfPageDown),
+        (ctrl(Char('u')), Action::HalfPageUp),
+        (ctrl(Char('h')), Action::HalfPageLeft),
+        (ctrl(Char('l')), Action::HalfPageRight),
+        (key(PageDown), Action::PageDown),
+        (key(PageUp), Action::PageUp),
+        (key(Home), Action::LeftEdge),
+        (key(End), Action::RightEdge),
+        (key(Char('g')), Action::Top),
+        (key(Char('G')), Action::Bottom),
+        (ctrl(Home), Action::TopLeft),
+        (ctrl(End), Action::BottomRight),
+        (ctrl(Char('g')), Action::GoTo),
+        (ctrl(Char('n')), Action::Reseed),
+        (key(Char('o')), Action::CenterOnLand),
+        (key(Char('<')), Action::ZoomIn),
+        (key(Char('>')), Action::ZoomOut),
+        (key(Char('f')), Action::Fit),
+        (ctrl(Char('r')), Action::ToggleRulers),
+        (alt(Char('r')), Action::ToggleBorderRulers),
+        (alt(Char('x')), Action::ToggleXRuler),
+        (alt(Char('y')), Action::ToggleYRuler),
+        (alt(Char('X')), Action::ToggleBottomRuler),
+        (alt(Char('Y')), Action::ToggleRightRuler),
+        (alt(Char('b')), Action::ToggleHalfBlock),
+        (alt(Char('t')), Action::ToggleTextured),
+        (alt(Char('h')), Action::ToggleHeatmap),
+        (alt(Char('e')), Action::ToggleElevation),
+        (key(Char('#')), Action::ToggleGrid),
+        (key(F(1)), Action::ToggleHud),
+        (key(Char('?')), Action::ToggleHelp),
+        (key(Char('i')), Action::ToggleInspector),
+        (key(Char('m')), Action::ToggleMinimap),
+        (key(Char('%')), Action::ToggleStats),
+        (key(Char('z')), Action::ResetView),
+        (key(Char('x')), Action::SwapButtons),
+        (key(Char('[')), Action::ShrinkBrush),
+        (key(Char(']')), Action::GrowBrush),
+        (ctrl(Char('b')), Action::SetBrushSize),
+        (key(Char('w')), Action::ToggleWalls),
+        (key(Char('B')), Action::ToggleSmoothBrush),
+        (key(Char('M')), Action::CycleMask),
+        (key(Char('b')), Action::BucketFill),
+        (key(Char('c')), Action::ClearMap),
+        (key(Char('C')), Action::FillMap),
+        (key(Char('v')), Action::ToggleLasso),
+        (key(Char('L')), Action::ToggleLineTool),
+        (key(Char('R')), Action::ToggleRectTool),
+        (key(Char('K')), Action::ToggleLock),
+        (key(Char('P')), Action::TogglePin),
+        (key(Char('n')), Action::NextPin),
+        (key(Char('N')), Action::PreviousPin),
+        (key(Char('s')), Action::CycleStamp),
+        (key(Char('S')), Action::SaveStamp),
+        (key(Char('a')), Action::Annotate),
+        (key(Char('A')), Action::RemoveNote),
+        (key(Char(' ')), Action::TogglePause),
+        (key(Char('p')), Action::NextPreset),
+        (key(Char('+')), Action::RaiseSeaLevel),
+        (key(Char('=')), Action::RaiseSeaLevel),
+        (key(Char('-')), Action::LowerSeaLevel),
+        (ctrl(Char('w')), Action::SetSeaLevel),
+    ]
+}
+
+/// Reads a JSON or RON list of `{ key, action }` bindings, by extension.
+/// A key given two different actions is reported and keeps the first.
+pub fn load_keymap(path: &Path) -> io::Result<Keymap> {
+    let content = fs::read_to_string(path)?;
+    let bindings: Vec<Binding> = save::decode(&content, path)?;
+
+    let mut keymap: Keymap = Vec::new();
+    for Binding { key: name, action } in bindings {
+        let Some(key) = KeyCombo::parse(&name) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown key '{}'", name),
+            ));
+        };
+        match keymap.iter().find(|(bound, _)| *bound == key) {
+            Some((_, first)) if *first != action => log::warn!(
+                "{}: '{}' is bound to both {:?} and {:?}, keeping {:?}",
+                path.display(),
+                name,
+                first,
+                action,
+                first
+            ),
+            _ => keymap.push((key, action)),
+        }
+    }
+    Ok(keymap)
+}
+
+pub fn resolve(keymap: &Keymap, key: &KeyEvent) -> Option<Action> {
+    let pressed = KeyCombo::new(key.code, key.modifiers);
+    keymap
+        .iter()
+        .find(|(combo, _)| *combo == pressed)
+        .map(|(_, action)| *action)
+}
@@ -1,28 +1,157 @@
+mod browser;
+mod cli;
+mod command;
+mod coords;
+mod diff;
+mod document;
+mod export;
+mod fill;
+mod history;
+mod hud;
+mod inspector;
+mod keymap;
+mod minimap;
+mod number_input;
+mod prefs;
+mod progress;
+mod prompt;
+mod save;
+mod selection;
+mod stamp;
+mod stats;
+mod terrain;
+mod theme;
+mod undo;
+
 use crossterm::event::{
-    self, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton, MouseEventKind,
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+    MouseButton, MouseEventKind,
 };
-use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
-use noise::{NoiseFn, Perlin};
+use crossterm::style::Print;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, SetTitle};
 use rand::Rng;
 use ratatui::backend::CrosstermBackend;
+use ratatui::buffer::Buffer;
 use ratatui::layout::{Alignment, Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span, Text};
-use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Widget};
 use ratatui::Terminal;
 use simplelog::{CombinedLogger, Config, LevelFilter, WriteLogger};
-use std::fs::File;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::fmt::Write as _;
 use std::io;
+use std::path::Path;
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-const MAP_WIDTH: usize = 200;
-const MAP_HEIGHT: usize = 200;
+use browser::{BrowserEvent, FileBrowser};
+use command::{Command, Target};
+use coords::Viewport;
+use document::Document;
+use history::History;
+use keymap::Action;
+use number_input::{NumberEvent, NumberInput};
+use prefs::{Prefs, Tiers};
+use progress::Task;
+use prompt::{Prompt, PromptEvent};
+use selection::{Lasso, Selection};
+use terrain::{NoiseParams, Preset, StartMap};
+use theme::Theme;
+use undo::UndoStack;
+
+/// Map size without `--width` and `--height`.
+const DEFAULT_MAP_WIDTH: usize = 200;
+const DEFAULT_MAP_HEIGHT: usize = 200;
 const RULLER_LEFT_SIZE: usize = 4;
+const RULLER_RIGHT_SIZE: usize = 4;
 const RULLER_UP_SIZE: usize = 1;
 const RULLER_DOWN_SIZE: usize = 1;
 
 const FILLED: char = '█';
 const EMPTY: char = '░';
+const HALF_BLOCK_UPPER: char = '▀';
+const ANNOTATION: char = '*';
+/// Pins past the ninth, which have no digit of their own.
+const PIN: char = '^';
+/// Start of a line waiting for its second click.
+const ANCHOR: char = '+';
+/// NaN cell, e.g. a hole in imported data.
+const MISSING: char = '?';
+/// Infinite cell, or one far outside `VALUE_LIMIT`.
+const OUT_OF_RANGE: char = '!';
+// Variants for textured rendering; repeats weight the common glyph
+const FILLED_VARIANTS: [char; 3] = [FILLED, FILLED, '▓'];
+const EMPTY_VARIANTS: [char; 4] = [EMPTY, EMPTY, EMPTY, '~'];
+// Elevation tiers; deep water and grass keep the plain glyphs
+const SHALLOW_WATER: char = '▒';
+const SAND: char = '▓';
+const MOUNTAIN: char = '▲';
+const SNOW: char = '△';
+const STATUS_BAR_SIZE: u16 = 1;
+
+/// How long the first key of a sequence such as `g g` waits for the second.
+const SEQUENCE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Cells at or below this value are water, everything above is land.
+const WATER_LEVEL: f64 = 0.;
+
+/// Largest magnitude a cell is expected to hold. Noise stays within about
+/// ±1, drawing writes 0 or 1, walls are 2 and the island falloff reaches
+/// about -2; anything past this is bad data rather than terrain.
+const VALUE_LIMIT: f64 = 4.;
+
+/// "Leave this cell alone" value for stamps and overlays. Writing it is a
+/// no-op, so a map cell only holds NaN when loaded data had a hole, and it
+/// renders as `MISSING`.
+const TRANSPARENT: f64 = f64::NAN;
+
+/// Wall terrain. Exact value so noise never produces it; walls count as land.
+const WALL: f64 = 2.;
+
+fn is_wall(value: f64) -> bool {
+    value == WALL
+}
+
+fn is_transparent(value: f64) -> bool {
+    value.is_nan()
+}
+
+#[global_allocator]
+static ALLOCATOR: hud::CountingAllocator = hud::CountingAllocator;
+
+/// XTWINOPS sequences stashing the terminal's own title and bringing it
+/// back. Terminals without a title stack ignore them.
+const PUSH_TITLE: &str = "\x1b[22;0t";
+const POP_TITLE: &str = "\x1b[23;0t";
+
+/// Written to the working directory unless `GAMELIVE_LOG` names another file.
+const LOG_FILE: &str = "gamelive.log";
+
+const MAX_BRUSH_SIZE: usize = 25;
+
+/// Written by `e` and `E`, to the working directory.
+const TEXT_FILE: &str = "map.txt";
+const VIEW_FILE: &str = "view.txt";
+/// Written by Ctrl+e.
+const PGM_FILE: &str = "map.pgm";
+
+/// How far `+` and `-` move the preset's water level.
+const SEA_LEVEL_STEP: f64 = 0.05;
+
+/// Ctrl+wheel steps per unit of paint value.
+const WHEEL_STEPS: f64 = 20.;
+
+/// Cells the view pans per wheel notch, times the zoom.
+const WHEEL_PAN: usize = 3;
+
+/// Status message for work refused while a regeneration or erosion is still
+/// running.
+const BUSY: &str = "still working on the map";
 
 const TARGET_FPS: u32 = 60;
 const FRAME_DURATION: Duration = Duration::from_micros(1_000_000 / TARGET_FPS as u64);
@@ -32,340 +161,2808 @@ struct Point {
   y: usize,
 }
 
+type Tui = Terminal<CrosstermBackend<io::Stdout>>;
+
+/// Notes attached to map cells, keyed by `(x, y)`.
+type Annotations = HashMap<(usize, usize), String>;
+
+/// Cells that edits leave alone, a flag per map cell.
+type Locks = Vec<Vec<bool>>;
+
+/// Map dimensions in cells, set for the session by `--width` and `--height`.
+/// Code holding a map reads them off it with `MapSize::of`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct MapSize {
+    width: usize,
+    height: usize,
+}
+
+impl MapSize {
+    fn of(map: &[Vec<f64>]) -> Self {
+        MapSize {
+            width: map.first().map_or(0, Vec::len),
+            height: map.len(),
+        }
+    }
+
+    fn contains(self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height
+    }
+
+    /// `height` rows of `width` copies of `value`, indexed `[y][x]`.
+    fn grid<T: Clone>(self, value: T) -> Vec<Vec<T>> {
+        vec![vec![value; self.width]; self.height]
+    }
+
+    /// The whole map as a region for `spawn_regen` and `Regen`.
+    fn region(self) -> (usize, usize, usize, usize) {
+        (0, 0, self.width, self.height)
+    }
+}
+
+impl Default for MapSize {
+    fn default() -> Self {
+        MapSize {
+            width: DEFAULT_MAP_WIDTH,
+            height: DEFAULT_MAP_HEIGHT,
+        }
+    }
+}
+
+/// Everything drawn over the terrain.
+struct Overlay<'a> {
+    annotations: &'a Annotations,
+    /// Numbered markers, in the order they were dropped.
+    pins: &'a [(usize, usize)],
+    selection: Option<&'a Selection>,
+    lasso: Option<&'a Lasso>,
+    /// Start of the line being drawn with the line tool.
+    anchor: Option<(usize, usize)>,
+    /// Cell under the keyboard cursor.
+    cursor: Option<(usize, usize)>,
+    /// The map against the `:diff` comparison, from `diff::diff_maps`.
+    diff: Option<&'a [Vec<std::cmp::Ordering>]>,
+    locked: &'a Locks,
+}
+
+/// What a submitted prompt is for.
+enum PromptAction {
+    Annotate(usize, usize),
+    /// `:` command line, shown in the status bar rather than a popup.
+    Command,
+    /// Name for a new file in a directory picked in the file browser.
+    NewFile(std::path::PathBuf, FileAction),
+}
+
+/// What a number typed into a `NumberInput` is used for.
+enum NumberAction {
+    /// Seed to regenerate the whole map from.
+    Seed,
+    /// Column of the cell to centre the view on, asked for before its row.
+    GoToX,
+    GoToY(usize),
+    BrushSize,
+    /// The preset's water level, in hundredths.
+    SeaLevel,
+}
+
+/// What a file picked in the file browser is used for.
+#[derive(Clone, Copy)]
+enum FileAction {
+    Load,
+    Save,
+    Export,
+    Import,
+}
+
+impl FileAction {
+    fn command(self, path: std::path::PathBuf) -> Command {
+        match self {
+            FileAction::Load => Command::Edit(Target::Path(path)),
+            FileAction::Save => Command::Write(Target::Path(path)),
+            FileAction::Export => Command::Export(Some(path)),
+            FileAction::Import => Command::Import(Some(path)),
+        }
+    }
+}
+
+/// What the mouse buttons draw.
+struct Brush {
+    left: f64,
+    right: f64,
+    /// Left button draws walls instead of `left`.
+    walls: bool,
+    /// Left button softens the cells it passes over instead of drawing.
+    smooth: bool,
+    /// Only cells passing this are painted over.
+    mask: Option<PaintMask>,
+    /// Side of the square both buttons paint, in cells.
+    size: usize,
+}
+
+impl Brush {
+    fn swap(&mut self) {
+        std::mem::swap(&mut self.left, &mut self.right);
+    }
+
+    /// Grows or shrinks the square by one cell a side, from 1 up to
+    /// `MAX_BRUSH_SIZE`.
+    fn resize(&mut self, grow: bool) {
+        self.size = if grow {
+            (self.size + 1).min(MAX_BRUSH_SIZE)
+        } else {
+            self.size.saturating_sub(1).max(1)
+        };
+    }
+
+    /// Moves the left value `notches` wheel steps up or down, staying within
+    /// the noise range.
+    fn step_left(&mut self, notches: f64) {
+        let steps = (self.left * WHEEL_STEPS).round() + notches;
+        self.left = (steps / WHEEL_STEPS).clamp(-1., 1.);
+    }
+
+    /// Whether the mask lets a cell holding `value` be painted.
+    fn allows(&self, value: f64, water_level: f64) -> bool {
+        match self.mask {
+            None => true,
+            Some(PaintMask::Water) => value <= water_level,
+            Some(PaintMask::Land) => value > water_level && value != WALL,
+        }
+    }
+}
+
+/// Cells the mouse may paint over, judged by the current preset's water
+/// level. Walls, missing and out-of-range cells match neither.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PaintMask {
+    Water,
+    Land,
+}
+
+impl PaintMask {
+    /// Off, water, land, then off again.
+    fn cycle(mask: Option<PaintMask>) -> Option<PaintMask> {
+        match mask {
+            None => Some(PaintMask::Water),
+            Some(PaintMask::Water) => Some(PaintMask::Land),
+            Some(PaintMask::Land) => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            PaintMask::Water => "water",
+            PaintMask::Land => "land",
+        }
+    }
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Brush {
+            left: 1.,
+            right: 0.,
+            walls: false,
+            smooth: false,
+            mask: None,
+            size: 1,
+        }
+    }
+}
+
+/// Tools drawn with two clicks: the first anchors, the second draws.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Line,
+    Rect,
+}
+
+impl Shape {
+    fn name(self) -> &'static str {
+        match self {
+            Shape::Line => "line",
+            Shape::Rect => "rectangle",
+        }
+    }
+}
+
 #[derive(PartialEq, Eq)]
 enum GameState {
   Draw,
   Run,
 }
 
-impl GameState {
-  fn togle_pause(&mut self) {
-    if *self == GameState::Run {
-      *self = GameState::Draw;
-    } else {
-      *self = GameState::Run
+impl GameState {
+  fn togle_pause(&mut self) {
+    if *self == GameState::Run {
+      *self = GameState::Draw;
+    } else {
+      *self = GameState::Run
+    }
+  }
+}
+
+fn random_field(size: MapSize) -> Point {
+    let mut rng = rand::thread_rng();
+    Point {x: rng.gen_range(0..size.width), y: rng.gen_range(0..size.height)}
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let command = match cli::parse_args(std::env::args().skip(1)) {
+        Ok(cli::Command::Help) => {
+            println!("{}", cli::USAGE);
+            return Ok(());
+        }
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(err) = init_logger() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+
+    let args = match command {
+        cli::Command::Gen(args) => return Ok(generate_batch(&args)?),
+        cli::Command::Tui(args) => args,
+        cli::Command::Help => unreachable!(),
+    };
+
+    let defaults = MapSize::default();
+    let map_size = MapSize {
+        width: args.width.unwrap_or(defaults.width),
+        height: args.height.unwrap_or(defaults.height),
+    };
+
+    // Read before the terminal is set up; crossterm then takes its events
+    // from /dev/tty since stdin is not a terminal
+    let piped_map = if args.stdin {
+        let mut text = String::new();
+        let parsed = io::Read::read_to_string(&mut io::stdin(), &mut text)
+            .map(|_| export::import_ascii(&text, map_size));
+        match parsed {
+            Ok(map) => Some(map),
+            Err(err) => {
+                eprintln!("error: failed to read a map from stdin: {}", err);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        None
+    };
+
+    let prefs_path = prefs::prefs_path();
+    let mut prefs = prefs::load_prefs(&prefs_path);
+    if let Some(x_tick) = args.x_tick {
+        prefs.x_tick = x_tick;
+    }
+    if let Some(y_tick) = args.y_tick {
+        prefs.y_tick = y_tick;
+    }
+    let mut noise = NoiseParams::new(rand::random());
+    if let Some(frequency) = args.frequency {
+        noise.frequency = frequency;
+    }
+    if let Some(octaves) = args.octaves {
+        noise.octaves = octaves;
+    }
+    if let Some(persistence) = args.persistence {
+        noise.persistence = persistence;
+    }
+    noise.island = args.island_mask.map(|falloff| terrain::IslandMask {
+        strength: args.island_strength.unwrap_or(1.),
+        falloff,
+    });
+    let start_name = args.start.as_deref().unwrap_or(&prefs.start);
+    let start = StartMap::parse(start_name).unwrap_or_else(|| {
+        log::warn!("unknown start map '{}', starting empty", start_name);
+        StartMap::Empty
+    });
+    // A loaded map brings its own size; only one given on the command line
+    // has to match it
+    let loaded_map = args.load.as_ref().map(|path| {
+        let loaded = save::load_map(path, prefs.transpose_files, None).and_then(|loaded| {
+            let size = MapSize::of(&loaded.0);
+            let flags = [
+                ("--width", args.width, size.width),
+                ("--height", args.height, size.height),
+            ];
+            let conflicts = |&(_, given, found): &(_, Option<usize>, _)| {
+                given.is_some_and(|given| given != found)
+            };
+            match flags.into_iter().find(conflicts) {
+                Some((flag, Some(given), _)) => {
+                    let message = format!(
+                        "it is a {}x{} map, but {} is {}",
+                        size.width, size.height, flag, given
+                    );
+                    Err(io::Error::new(io::ErrorKind::InvalidData, message))
+                }
+                _ => Ok(loaded),
+            }
+        });
+        loaded.unwrap_or_else(|err| {
+            eprintln!("error: failed to load {}: {}", path.display(), err);
+            std::process::exit(1);
+        })
+    });
+    let (mut map, mut locked, mut annotations) = piped_map
+        .map(|map| (map, no_locks(map_size), Annotations::new()))
+        .or(loaded_map)
+        .unwrap_or_else(|| {
+            let map = start_map(start, &noise, map_size);
+            (map, no_locks(map_size), Annotations::new())
+        });
+    let map_size = MapSize::of(&map);
+
+    let mut presets = terrain::builtin_presets();
+    for file in ["presets.json", "presets.ron"] {
+        let presets_path = prefs::config_dir().join(file);
+        match terrain::load_presets(&presets_path) {
+            Ok(loaded) => presets.extend(loaded),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => log::warn!("failed to load presets {}: {}", presets_path.display(), err),
+        }
+    }
+
+    if let Some(script) = &args.exec {
+        let result = run_script(
+            script,
+            &mut map,
+            &mut locked,
+            &mut annotations,
+            &mut noise,
+            &mut prefs,
+            &presets,
+        );
+        if let Err(err) = result {
+            eprintln!("error: {}", err);
+            std::process::exit(1);
+        }
+    }
+    if args.dump {
+        let region = MapSize::of(&map).region();
+        let text = export::ascii_text(&map, region, false, WATER_LEVEL);
+        // A closed pipe, e.g. `| head`, is not an error
+        return match io::Write::write_all(&mut io::stdout(), text.as_bytes()) {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+            result => Ok(result?),
+        };
+    }
+    if args.exec.is_some() {
+        return Ok(());
+    }
+
+    let (mut terminal, mouse) = setup_terminal()?;
+    // A panic would otherwise leave the shell in raw mode on the alternate
+    // screen, with the message drawn over by the map
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        log::error!("{}", info);
+        let _ = restore_terminal(mouse);
+        default_hook(info);
+    }));
+
+    // Where the camera is headed; keys move this
+    let mut camera_x = 0;
+    let mut camera_y = 0;
+    // Where the camera is drawn, catching up with `camera_x`/`camera_y`
+    let mut camera_shown = (0, 0);
+    let history_path = history::history_path();
+    let mut history = if prefs.save_history {
+        history::load_history(&history_path)
+    } else {
+        History::default()
+    };
+    let mut game_state = GameState::Draw;
+    let stamps_dir = prefs::config_dir().join("stamps");
+    let mut stamps = stamp::builtin_stamps();
+    stamps.extend(stamp::load_stamp_dir(&stamps_dir));
+    let mut selected_stamp: Option<usize> = None;
+    let mut fit_to_screen = false;
+    let mut pins: Vec<(usize, usize)> = Vec::new();
+    // Pin the camera jumped to last
+    let mut current_pin: Option<usize> = None;
+    let mut hover: Option<(usize, usize)> = None;
+    let mut selection: Option<Selection> = None;
+    // Moved by the arrows and hjkl instead of the camera while shown
+    let mut cursor: Option<(usize, usize)> = None;
+    let mut brush = Brush::default();
+    // While on, left drags draw a lasso instead of painting
+    let mut lasso_mode = false;
+    let mut lasso: Option<Lasso> = None;
+    // While set, two left clicks draw a line or box between them
+    let mut shape_tool: Option<Shape> = None;
+    let mut shape_anchor: Option<(usize, usize)> = None;
+    // Map loaded with `:diff`, changes against it are highlighted
+    let mut comparison: Option<Vec<Vec<f64>>> = None;
+    // Cell written last during the current mouse drag
+    let mut last_painted: Option<(usize, usize)> = None;
+    // A mouse button is held on the map, so what it paints is one edit so far
+    let mut stroke = false;
+    let mut prompt: Option<(Prompt, PromptAction)> = None;
+    let mut browser: Option<(FileBrowser, FileAction)> = None;
+    let mut number_input: Option<(NumberInput, NumberAction)> = None;
+    let mut status_message: Option<String> = None;
+    if !mouse {
+        status_message = Some("no mouse: draw with V, then Enter / Backspace".to_string());
+    }
+    // Inverts the map for one frame when an action is refused
+    let mut flash = false;
+    // Whether the next frame needs drawing at all
+    let mut dirty = true;
+    let mut hud = hud::Hud::default();
+    let mut show_hud = false;
+    // Counted when the panel opens, not kept up to date
+    let mut stats: Option<stats::MapStats> = None;
+    // First key of a two-key sequence such as `z z`
+    let mut pending_key: Option<(char, Instant)> = None;
+    // Digits typed before a movement key, as in `10j`
+    let mut pending_count: Option<usize> = None;
+    let mut document = Document::new(args.load.clone(), &map, &locked);
+    let mut undo = UndoStack::new(&map);
+    // Title last sent to the terminal
+    let mut title = String::new();
+    // Regeneration still running on a worker thread
+    let mut background: Option<Task<Regen>> = None;
+    let mut current_preset = 0;
+    // Bindings from the config dir come first and win over the defaults
+    let mut keymap = Vec::new();
+    for file in ["keys.json", "keys.ron"] {
+        let keys_path = prefs::config_dir().join(file);
+        match keymap::load_keymap(&keys_path) {
+            Ok(loaded) => keymap.extend(loaded),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => log::warn!("failed to load keys {}: {}", keys_path.display(), err),
+        }
+    }
+    keymap.extend(keymap::default_keymap());
+    let mut themes = theme::builtin_themes();
+    for file in ["themes.json", "themes.ron"] {
+        let themes_path = prefs::config_dir().join(file);
+        match theme::load_themes(&themes_path) {
+            Ok(loaded) => themes.extend(loaded),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => log::warn!("failed to load themes {}: {}", themes_path.display(), err),
+        }
+    }
+    let found_theme = themes.iter().position(|t| t.name == prefs.theme);
+    let mut current_theme = found_theme.unwrap_or_else(|| {
+        log::warn!("unknown theme '{}', using the default", prefs.theme);
+        0
+    });
+    let last_frame = Instant::now();
+
+    #[cfg(unix)]
+    let suspend_requested = {
+        let flag = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(signal_hook::consts::SIGTSTP, Arc::clone(&flag))?;
+        flag
+    };
+    // Not every terminal reports resizes as events, so catch the signal too
+    #[cfg(unix)]
+    let resized = {
+        let flag = Arc::new(AtomicBool::new(false));
+        signal_hook::flag::register(signal_hook::consts::SIGWINCH, Arc::clone(&flag))?;
+        flag
+    };
+
+    'main_loop:loop {
+        #[cfg(unix)]
+        if suspend_requested.swap(false, Ordering::Relaxed) {
+            suspend(&mut terminal, mouse)?;
+            title.clear();
+            dirty = true;
+        }
+        #[cfg(unix)]
+        if resized.swap(false, Ordering::Relaxed) {
+            terminal.autoresize()?;
+            dirty = true;
+        }
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(last_frame);
+
+        if elapsed >= FRAME_DURATION {
+            if game_state == GameState::Run {
+                  let point = random_field(map_size);
+                  draw_on_map(&mut map, &locked, point.x, point.y, 1.);
+                  dirty = true;
+                  //for y in 0..MAP_HEIGHT {
+                  //    for x in 0..MAP_WIDTH {
+                  //        const NEIGHBORS: [(isize, isize); 8] = [
+                  //            (-1, -1), (-1, 0), (-1, 1),
+                  //            (0, -1),          (0, 1),
+                  //            (1, -1),  (1, 0),  (1, 1)];
+
+                  //        let mut neighbords_count = 0;
+
+                  //        for (dx, dy) in NEIGHBORS {
+                  //            let x = (x as isize + dx) % 20;
+                  //            let y = (y as isize + dy) % 20;
+
+                  //            if 0 <= x && x < (MAP_WIDTH - 1) as isize 
+                  //                && 0 <= y && y < (MAP_HEIGHT - 1) as isize {
+                  //                neighbords_count += 1;
+                  //            }
+                  //        }
+
+                  //        log::info!("neighbords_count: {}", neighbords_count);
+
+                  //        if neighbords_count < 2 || 3 < neighbords_count {
+                  //            draw_on_map(&mut map, x, y, 0.);
+                  //        }
+                  //        if neighbords_count == 3 {
+                  //            draw_on_map(&mut map, x, y, 1.);
+                  //        }
+                  //    }
+                  //}
+            }
+
+            if let Some(task) = background.take() {
+                if task.is_finished() {
+                    status_message = Some(task.join().apply(&mut map, &locked));
+                } else {
+                    if let Some(status) = task.status() {
+                        status_message = Some(status);
+                    }
+                    background = Some(task);
+                }
+                dirty = true;
+            }
+            
+            if dirty && fit_to_screen {
+                let size = terminal.size()?;
+                let (main_area, _) = split_status_bar(Rect::new(0, 0, size.width, size.height));
+                prefs.zoom = coords::fit_zoom(main_area, map_size, &prefs);
+                camera_x = 0;
+                camera_y = 0;
+            }
+
+            if camera_shown != (camera_x, camera_y) {
+                camera_shown = if prefs.smooth_pan {
+                    (ease(camera_shown.0, camera_x), ease(camera_shown.1, camera_y))
+                } else {
+                    (camera_x, camera_y)
+                };
+                dirty = true;
+            }
+
+            if dirty {
+                let new_title = document.title(&map, &locked);
+                if new_title != title {
+                    crossterm::execute!(io::stdout(), SetTitle(&new_title))?;
+                    title = new_title;
+                }
+
+                let draw_started = Instant::now();
+                let allocations = hud::allocations();
+                terminal.draw(|f| {
+                    let (area, status_area) = split_status_bar(f.area());
+                    let (shown_x, shown_y) = camera_shown;
+                    let viewport = Viewport::new(area, shown_x, shown_y, map_size, &prefs);
+
+                    let preset = &presets[current_preset];
+                    let theme = &themes[current_theme];
+                    let selection = selection.as_ref();
+                    let diff = comparison.as_ref().map(|other| diff::diff_maps(&map, other));
+                    let overlay = Overlay {
+                        annotations: &annotations,
+                        pins: &pins,
+                        selection,
+                        lasso: lasso.as_ref(),
+                        anchor: shape_anchor,
+                        cursor,
+                        diff: diff.as_deref(),
+                        locked: &locked,
+                    };
+                    let map_view = MapView {
+                        map: &map,
+                        overlay: &overlay,
+                        preset,
+                        theme,
+                        viewport: &viewport,
+                        prefs: &prefs,
+                    };
+                    let ruled_area = coords::ruled_area(viewport.area, &prefs).intersection(area);
+                    f.render_widget(map_view, ruled_area);
+                    if flash {
+                        let inverted = Style::default().add_modifier(Modifier::REVERSED);
+                        f.buffer_mut().set_style(ruled_area, inverted);
+                    }
+                    match &prompt {
+                        Some((prompt, PromptAction::Command)) => {
+                            let command_line = Paragraph::new(format!(":{}_", prompt.input));
+                            f.render_widget(command_line, status_area);
+                        }
+                        _ => {
+                            // The seed stays in the corner so a map can be made again
+                            let water_level = presets[current_preset].water_level;
+                            let seed = format!(" sea {:.2} | seed {} ", water_level, noise.seed);
+                            let parts = Layout::default()
+                                .direction(Direction::Horizontal)
+                                .constraints([
+                                    Constraint::Min(0),
+                                    Constraint::Length(seed.len() as u16),
+                                ])
+                                .split(status_area);
+                            let status = status_line(
+                                cursor.or(hover).map(|(x, y)| {
+                                    let note = annotations.get(&(x, y)).map(String::as_str);
+                                    ((x, y), map[y][x], note)
+                                }),
+                                &viewport,
+                                (prefs.x_tick, prefs.y_tick),
+                                selection,
+                                lasso_mode.then_some(lasso.as_ref()),
+                                &brush,
+                                document.slot,
+                                status_message.as_deref(),
+                            );
+                            f.render_widget(status.style(theme.status_style()), parts[0]);
+                            let seed = Paragraph::new(seed).style(theme.status_style());
+                            f.render_widget(seed, parts[1]);
+                        }
+                    }
+
+                    // Follows the mouse, or the keyboard or selection cursor when shown
+                    let inspected =
+                        selection.map(|selection| selection.cursor).or(cursor).or(hover);
+                    if let (true, Some(cell)) = (prefs.show_inspector, inspected) {
+                        inspector::render(f, viewport.area, &map, cell, &viewport, theme);
+                    }
+
+                    if prefs.show_minimap {
+                        minimap::render(f, &map, &viewport, preset, theme, &prefs);
+                    }
+
+                    if show_hud {
+                        let cells = viewport.area.width as usize * viewport.area.height as usize;
+                        hud.render(f, area, cells, theme);
+                    }
+
+                    if let Some(stats) = &stats {
+                        let stats_area = centered_rect(40, 60, area);
+                        f.render_widget(Clear, stats_area);
+                        f.render_widget(stats::paragraph(stats, theme), stats_area);
+                    }
+
+                    if prefs.show_help {
+                        let help_area = centered_rect(60, 60, area);
+                        f.render_widget(Clear, help_area); 
+                        let help_paragraph = create_help_paragraph(theme, mouse);
+                        f.render_widget(help_paragraph, help_area);
+                    }
+
+                    if let Some((browser, _)) = &browser {
+                        browser.render(f, area, theme);
+                    }
+
+                    if let Some((input, _)) = &number_input {
+                        input.render(f, f.area(), theme);
+                    }
+
+                    match &prompt {
+                        Some((_, PromptAction::Command)) | None => {}
+                        Some((prompt, _)) => prompt.render(f, f.area(), theme),
+                    }
+                })?;
+                hud.record(draw_started.elapsed(), hud::allocations() - allocations);
+                // A flashed frame needs one more to clear it
+                dirty = flash;
+                flash = false;
+            }
+        }
+
+        while event::poll(Duration::from_millis(0))? {
+             let size = terminal.size()?;
+             let (main_area, _) = split_status_bar(Rect::new(0, 0, size.width, size.height));
+             let viewport = Viewport::new(main_area, camera_x, camera_y, map_size, &prefs);
+             camera_x = viewport.camera_x;
+             camera_y = viewport.camera_y;
+
+             let zoom = prefs.zoom;
+             // In map cells rather than screen characters
+             let view_width = viewport.map_width();
+             let view_height = viewport.map_height();
+             let half_width = view_width / 2;
+             let half_height = view_height / 2;
+
+             let (width, height) = viewport.max_camera();
+             // The mouse points at what is drawn, not where the camera is headed
+             let (shown_x, shown_y) = camera_shown;
+             let shown = Viewport::new(main_area, shown_x, shown_y, map_size, &prefs);
+
+             // Set when a movement is blocked by the map edge
+             let mut edge = false;
+
+             // Changes since the previous event are one undo step, a whole
+             // stroke once its button is let go
+             if game_state == GameState::Run {
+                 undo.rebase(&map);
+             } else if !stroke {
+                 undo.record(&map);
+             }
+
+             if event::poll(std::time::Duration::from_millis(100))? {
+                 dirty = true;
+                 let event = event::read()?;
+                 // Any other key cancels a pending sequence, as does waiting too long
+                 let prefix = match event {
+                     Event::Key(_) => pending_key
+                         .take()
+                         .filter(|(_, pressed)| pressed.elapsed() < SEQUENCE_TIMEOUT)
+                         .map(|(key, _)| key),
+                     _ => None,
+                 };
+                 let count = match event {
+                     Event::Key(_) => pending_count.take(),
+                     _ => None,
+                 };
+                 match event {
+                     Event::Key(key)
+                         if matches!(prompt, Some((_, PromptAction::Command)))
+                             && matches!(key.code, KeyCode::Up | KeyCode::Down) =>
+                     {
+                         let (input, _) = prompt.as_mut().unwrap();
+                         let recalled = if key.code == KeyCode::Up {
+                             history.previous(&input.input)
+                         } else {
+                             history.next()
+                         };
+                         if let Some(line) = recalled {
+                             input.input = line.to_string();
+                         }
+                     }
+                     Event::Key(key) if prompt.is_some() => {
+                         let (input, _) = prompt.as_mut().unwrap();
+                         match input.handle_key(key) {
+                             PromptEvent::Pending => {}
+                             PromptEvent::Cancel => {
+                                 history.reset();
+                                 prompt = None;
+                             }
+                             PromptEvent::Submit(text) => {
+                                 let (_, action) = prompt.take().unwrap();
+                                 if let PromptAction::Command = action {
+                                     history.push(&text);
+                                 }
+                                 match action {
+                                     PromptAction::Annotate(x, y) => {
+                                         let text = text.trim();
+                                         if text.is_empty() {
+                                             annotations.remove(&(x, y));
+                                         } else {
+                                             annotations.insert((x, y), text.to_string());
+                                         }
+                                     }
+                                     PromptAction::Command => match command::parse_command(&text) {
+                                         Ok(Command::Quit) => break 'main_loop,
+                                         Ok(Command::Preset(name)) => {
+                                             let found =
+                                                 presets.iter().position(|p| p.name == name);
+                                             status_message = Some(match found {
+                                                 Some(_) if background.is_some() => BUSY.into(),
+                                                 Some(i) => {
+                                                     current_preset = i;
+                                                     background = Some(apply_preset(
+                                                         &map,
+                                                         &presets[i],
+                                                         &mut noise,
+                                                     ));
+                                                     format!("preset {}", name)
+                                                 }
+                                                 None => format!("unknown preset '{}'", name),
+                                             });
+                                         }
+                                         Ok(Command::Theme(name)) => {
+                                             let found = themes.iter().position(|t| t.name == name);
+                                             status_message = Some(match found {
+                                                 Some(i) => {
+                                                     current_theme = i;
+                                                     let message = format!("theme {}", name);
+                                                     prefs.theme = name;
+                                                     message
+                                                 }
+                                                 None => format!("unknown theme '{}'", name),
+                                             });
+                                         }
+                                         Ok(Command::Diff(None)) => {
+                                             comparison = None;
+                                             status_message = Some("diff off".to_string());
+                                         }
+                                         Ok(Command::Diff(Some(path))) => {
+                                             let transpose = prefs.transpose_files;
+                                             let loaded =
+                                                 save::load_map(&path, transpose, Some(map_size));
+                                             status_message = Some(match loaded {
+                                                 Ok((other, _, _)) => {
+                                                     let changes = diff::diff_maps(&map, &other);
+                                                     let (higher, lower) =
+                                                         diff::count_changes(&changes);
+                                                     comparison = Some(other);
+                                                     format!(
+                                                         "comparing with {}: {} higher, {} lower",
+                                                         path.display(),
+                                                         higher,
+                                                         lower
+                                                     )
+                                                 }
+                                                 Err(err) => format!(
+                                                     "failed to load {}: {}",
+                                                     path.display(),
+                                                     err
+                                                 ),
+                                             });
+                                         }
+                                         Ok(
+                                             Command::Seed(_)
+                                             | Command::Regen
+                                             | Command::Erode { .. }
+                                             | Command::Rivers(_),
+                                         ) if background.is_some() => {
+                                             status_message = Some(BUSY.into());
+                                         }
+                                         Ok(Command::Seed(seed)) => {
+                                             background = Some(reseed(&map, &mut noise, seed));
+                                         }
+                                         Ok(Command::Regen) => {
+                                             let (x, y) = (camera_x, camera_y);
+                                             let region = (x, y, x + view_width, y + view_height);
+                                             let params = noise.reroll();
+                                             let done = "regenerated the view".to_string();
+                                             let task = spawn_regen(&map, region, params, done);
+                                             background = Some(task);
+                                         }
+                                         Ok(Command::Erode {
+                                             droplets,
+                                             iterations,
+                                         }) => {
+                                             let seed = rand::random();
+                                             let task =
+                                                 spawn_erode(&map, droplets, iterations, seed);
+                                             background = Some(task);
+                                         }
+                                         Ok(Command::Rivers(count)) => {
+                                             let water_level = presets[current_preset].water_level;
+                                             let rivers = carve_rivers(&map, count, water_level);
+                                             status_message = Some(rivers.apply(&mut map, &locked));
+                                         }
+                                         Ok(command) => match browser_for(&command) {
+                                             Some(picker) => browser = Some(picker),
+                                             None => {
+                                                 // Nothing picked out on the old map is
+                                                 // on the imported one
+                                                 let imports =
+                                                     matches!(command, Command::Import(_));
+                                                 let result = run_command(
+                                                     command,
+                                                     &mut map,
+                                                     &mut locked,
+                                                     &mut annotations,
+                                                     &mut document,
+                                                     &mut prefs,
+                                                     presets[current_preset].water_level,
+                                                 );
+                                                 if imports && result.is_ok() {
+                                                     selection = None;
+                                                     lasso = None;
+                                                 }
+                                                 status_message =
+                                                     Some(result.unwrap_or_else(|err| err));
+                                             }
+                                         },
+                                         Err(err) => status_message = Some(err),
+                                     },
+                                     PromptAction::NewFile(dir, action) => {
+                                         let name = text.trim();
+                                         if !name.is_empty() {
+                                             let command = action.command(dir.join(name));
+                                             let imports = matches!(command, Command::Import(_));
+                                             let result = run_command(
+                                                 command,
+                                                 &mut map,
+                                                 &mut locked,
+                                                 &mut annotations,
+                                                 &mut document,
+                                                 &mut prefs,
+                                                 presets[current_preset].water_level,
+                                             );
+                                             if imports && result.is_ok() {
+                                                 selection = None;
+                                                 lasso = None;
+                                             }
+                                             status_message =
+                                                 Some(result.unwrap_or_else(|err| err));
+                                         }
+                                     }
+                                 }
+                             }
+                         }
+                     }
+                     Event::Key(key) if number_input.is_some() => {
+                         let (input, _) = number_input.as_mut().unwrap();
+                         match input.handle_key(key) {
+                             NumberEvent::Pending => {}
+                             NumberEvent::Cancel => number_input = None,
+                             NumberEvent::Submit(value) => {
+                                 let (_, action) = number_input.take().unwrap();
+                                 match action {
+                                     NumberAction::Seed if background.is_some() => {
+                                         status_message = Some(BUSY.into());
+                                     }
+                                     NumberAction::Seed => {
+                                         let seed = value as u32;
+                                         background = Some(reseed(&map, &mut noise, seed));
+                                     }
+                                     NumberAction::GoToX => {
+                                         let max_y = map_size.height as isize - 1;
+                                         let input = NumberInput::new("Go to y", 0, max_y);
+                                         let action = NumberAction::GoToY(value as usize);
+                                         number_input = Some((input, action));
+                                     }
+                                     // The viewport clamps the camera where the cell
+                                     // cannot be centred
+                                     NumberAction::GoToY(x) => {
+                                         let y = viewport.display_y(value as usize);
+                                         camera_x = x.saturating_sub(view_width / 2);
+                                         camera_y = y.saturating_sub(view_height / 2);
+                                     }
+                                     NumberAction::BrushSize => brush.size = value as usize,
+                                     NumberAction::SeaLevel => {
+                                         let water_level = value as f64 / 100.;
+                                         presets[current_preset].water_level = water_level;
+                                     }
+                                 }
+                             }
+                         }
+                     }
+                     Event::Key(key) if browser.is_some() => {
+                         let (picker, action) = browser.as_mut().unwrap();
+                         let action = *action;
+                         match picker.handle_key(key) {
+                             BrowserEvent::Pending => {}
+                             BrowserEvent::Cancel => browser = None,
+                             BrowserEvent::Selected(path) => {
+                                 browser = None;
+                                 let command = action.command(path);
+                                 let imports = matches!(command, Command::Import(_));
+                                 let result = run_command(
+                                     command,
+                                     &mut map,
+                                     &mut locked,
+                                     &mut annotations,
+                                     &mut document,
+                                     &mut prefs,
+                                     presets[current_preset].water_level,
+                                 );
+                                 if imports && result.is_ok() {
+                                     selection = None;
+                                     lasso = None;
+                                 }
+                                 status_message = Some(result.unwrap_or_else(|err| err));
+                             }
+                             BrowserEvent::NewFile(dir) => {
+                                 browser = None;
+                                 let prompt_action = PromptAction::NewFile(dir, action);
+                                 prompt = Some((Prompt::new("File name", ""), prompt_action));
+                             }
+                         }
+                     }
+                     Event::Key(key) if selection.is_some() => {
+                         let current = selection.as_mut().unwrap();
+                         let step = zoom as isize;
+                         match key.code {
+                             KeyCode::Char('h') | KeyCode::Left => {
+                                 edge = !current.move_cursor(-step, 0);
+                             }
+                             KeyCode::Char('l') | KeyCode::Right => {
+                                 edge = !current.move_cursor(step, 0);
+                             }
+                             KeyCode::Char('k') | KeyCode::Up => {
+                                 edge = !current.move_cursor(0, -step);
+                             }
+                             KeyCode::Char('j') | KeyCode::Down => {
+                                 edge = !current.move_cursor(0, step);
+                             }
+                             KeyCode::Char('y') => {
+                                 let (x, y, w, h) = current.rect();
+                                 let cells = stamp::copy_region(&map, x, y, w, h);
+                                 selected_stamp = Some(keep_copy(&mut stamps, cells));
+                                 status_message = Some(format!("copied {}x{} as a stamp", w, h));
+                                 selection = None;
+                             }
+                             KeyCode::Char('f') => {
+                                 current.fill(&mut map, &locked, 1.);
+                                 selection = None;
+                             }
+                             KeyCode::Char('d') => {
+                                 current.fill(&mut map, &locked, 0.);
+                                 selection = None;
+                             }
+                             KeyCode::Char('K') => {
+                                 status_message = Some(if current.toggle_lock(&mut locked) {
+                                     "locked the selection".to_string()
+                                 } else {
+                                     "unlocked the selection".to_string()
+                                 });
+                                 selection = None;
+                             }
+                             KeyCode::Char('o') => {
+                                 let water_level = presets[current_preset].water_level;
+                                 let inside = |x, y, value| {
+                                     current.contains(x, y) && value > water_level
+                                 };
+                                 let outlined =
+                                     fill::outline_region(&mut map, &locked, inside, WALL);
+                                 status_message = Some(format!("outlined {} cells", outlined));
+                                 selection = None;
+                             }
+                             KeyCode::Char('r') if background.is_some() => {
+                                 status_message = Some(BUSY.into());
+                             }
+                             KeyCode::Char('r') => {
+                                 let (x, y, w, h) = current.rect();
+                                 let region = (x, y, x + w, y + h);
+                                 let done = "regenerated the selection".to_string();
+                                 background = Some(spawn_regen(&map, region, noise.reroll(), done));
+                                 selection = None;
+                             }
+                             // Paint and erase at the cursor, like the two mouse buttons
+                             KeyCode::Enter => {
+                                 let (x, y) = current.cursor;
+                                 let value = if brush.walls { WALL } else { brush.left };
+                                 let water_level = presets[current_preset].water_level;
+                                 paint_brush(&mut map, &locked, x, y, value, &brush, water_level);
+                             }
+                             KeyCode::Backspace => {
+                                 let (x, y) = current.cursor;
+                                 let water_level = presets[current_preset].water_level;
+                                 let value = brush.right;
+                                 paint_brush(&mut map, &locked, x, y, value, &brush, water_level);
+                             }
+                             KeyCode::Char('V') | KeyCode::Esc => selection = None,
+                             _ => {}
+                         }
+
+                         // Keep the cursor on screen
+                         if let Some(current) = &selection {
+                             let view = (view_width, view_height);
+                             (camera_x, camera_y) =
+                                 scroll_to_show(current.cursor, (camera_x, camera_y), view);
+                         }
+                     }
+                     Event::Key(key) if lasso.as_ref().is_some_and(Lasso::is_closed) => {
+                         let current = lasso.as_ref().unwrap();
+                         match key.code {
+                             KeyCode::Char('y') => {
+                                 let (_, _, w, h) = current.bounds();
+                                 selected_stamp = Some(keep_copy(&mut stamps, current.copy(&map)));
+                                 status_message = Some(format!("copied {}x{} as a stamp", w, h));
+                                 lasso = None;
+                             }
+                             KeyCode::Char('f') => {
+                                 current.fill(&mut map, &locked, 1.);
+                                 lasso = None;
+                             }
+                             KeyCode::Char('d') => {
+                                 current.fill(&mut map, &locked, 0.);
+                                 lasso = None;
+                             }
+                             KeyCode::Char('K') => {
+                                 status_message = Some(if current.toggle_lock(&mut locked) {
+                                     "locked the lasso".to_string()
+                                 } else {
+                                     "unlocked the lasso".to_string()
+                                 });
+                                 lasso = None;
+                             }
+                             KeyCode::Char('o') => {
+                                 let water_level = presets[current_preset].water_level;
+                                 let inside = |x, y, value| {
+                                     current.contains(x, y) && value > water_level
+                                 };
+                                 let outlined =
+                                     fill::outline_region(&mut map, &locked, inside, WALL);
+                                 status_message = Some(format!("outlined {} cells", outlined));
+                                 lasso = None;
+                             }
+                             KeyCode::Esc => lasso = None,
+                             _ => {}
+                         }
+                     }
+                     // Bound digits keep their action; a count never starts with 0
+                     Event::Key(
+                         key @ KeyEvent {
+                             code: KeyCode::Char(digit @ '0'..='9'),
+                             modifiers: KeyModifiers::NONE,
+                             ..
+                         },
+                     ) if keymap::resolve(&keymap, &key).is_none()
+                         && (count.is_some() || digit != '0') =>
+                     {
+                         let digit = digit.to_digit(10).unwrap() as usize;
+                         pending_count =
+                             Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                     }
+                     Event::Key(key) => match keymap::resolve(&keymap, &key) {
+                         Some(Action::Quit) => break 'main_loop,
+                         Some(Action::Suspend) => {
+                             #[cfg(unix)]
+                             {
+                                 suspend(&mut terminal, mouse)?;
+                                 title.clear();
+                             }
+                         }
+                         Some(Action::Select) => {
+                             // Starts at the keyboard cursor, under the mouse, or in the
+                             // middle of the view
+                             let (x, y) = cursor.or(hover).unwrap_or((
+                                 camera_x + view_width / 2,
+                                 camera_y + view_height / 2,
+                             ));
+                             selection = Some(Selection::new(x, y, map_size));
+                         }
+                         Some(Action::Command) => {
+                             status_message = None;
+                             prompt = Some((Prompt::new(":", ""), PromptAction::Command));
+                         }
+                         Some(action @ (Action::Save | Action::Open)) => {
+                             let command = if action == Action::Save {
+                                 Command::Write(document.target())
+                             } else {
+                                 Command::Edit(document.target())
+                             };
+                             let message = run_command(
+                                 command,
+                                 &mut map,
+                                 &mut locked,
+                                 &mut annotations,
+                                 &mut document,
+                                 &mut prefs,
+                                 presets[current_preset].water_level,
+                             )
+                             .unwrap_or_else(|err| err);
+                             status_message = Some(message);
+                         }
+                         Some(action @ (Action::ExportText | Action::ExportView)) => {
+                             let (path, region) = if action == Action::ExportText {
+                                 (TEXT_FILE, map_size.region())
+                             } else {
+                                 let (x, y) = (camera_x, camera_y);
+                                 (VIEW_FILE, (x, y, x + view_width, y + view_height))
+                             };
+                             let transpose = prefs.transpose_files;
+                             let water_level = presets[current_preset].water_level;
+                             let result = export::export_ascii(
+                                 &map,
+                                 region,
+                                 Path::new(path),
+                                 transpose,
+                                 water_level,
+                             );
+                             status_message = Some(match result {
+                                 Ok(()) => {
+                                     log::info!("exported {}", path);
+                                     format!("exported {}", path)
+                                 }
+                                 Err(err) => format!("failed to export {}: {}", path, err),
+                             });
+                         }
+                         Some(Action::ExportPgm) => {
+                             let command = Command::Export(Some(PGM_FILE.into()));
+                             let message = run_command(
+                                 command,
+                                 &mut map,
+                                 &mut locked,
+                                 &mut annotations,
+                                 &mut document,
+                                 &mut prefs,
+                                 presets[current_preset].water_level,
+                             )
+                             .unwrap_or_else(|err| err);
+                             status_message = Some(message);
+                         }
+                         Some(action @ (Action::Undo | Action::Redo)) => {
+                             let (count, verb) = if action == Action::Undo {
+                                 (undo.undo(&mut map), "undid")
+                             } else {
+                                 (undo.redo(&mut map), "redid")
+                             };
+                             status_message = Some(match count {
+                                 Some(count) => format!("{} {} cells", verb, count),
+                                 None if action == Action::Undo => "nothing to undo".into(),
+                                 None => "nothing to redo".into(),
+                             });
+                         }
+                         Some(Action::HalfPageDown) => {
+                             edge = camera_y >= height;
+                             camera_y = (camera_y + half_height).min(height);
+                             camera_y = snap_row(camera_y, true, &viewport, &prefs);
+                         }
+                         Some(Action::GoTo) => {
+                             let max_x = map_size.width as isize - 1;
+                             let input = NumberInput::new("Go to x", 0, max_x);
+                             number_input = Some((input, NumberAction::GoToX));
+                         }
+                         Some(Action::SetBrushSize) => {
+                             let input = NumberInput::new("Brush size", 1, MAX_BRUSH_SIZE as isize);
+                             number_input = Some((input, NumberAction::BrushSize));
+                         }
+                         Some(Action::SetSeaLevel) => {
+                             let input = NumberInput::new("Sea level in hundredths", -100, 100);
+                             number_input = Some((input, NumberAction::SeaLevel));
+                         }
+                         Some(Action::Reseed) => {
+                             let input = NumberInput::new("Seed", 0, u32::MAX as isize);
+                             number_input = Some((input, NumberAction::Seed));
+                         }
+                         Some(Action::HalfPageUp) => {
+                             edge = camera_y == 0;
+                             camera_y = camera_y.saturating_sub(half_height);
+                             camera_y = snap_row(camera_y, false, &viewport, &prefs);
+                         }
+                         Some(Action::HalfPageLeft) => {
+                             edge = camera_x == 0;
+                             camera_x = camera_x.saturating_sub(half_width);
+                         }
+                         Some(Action::HalfPageRight) => {
+                             edge = camera_x >= width;
+                             camera_x = (camera_x + half_width).min(width);
+                         }
+                         Some(Action::ToggleRulers) => {
+                             // Hides whichever are shown, or brings back the usual two
+                             let shown = prefs.show_x_ruler
+                                 || prefs.show_y_ruler
+                                 || prefs.show_bottom_ruler
+                                 || prefs.show_right_ruler;
+                             prefs.show_x_ruler = !shown;
+                             prefs.show_y_ruler = !shown;
+                             prefs.show_bottom_ruler = false;
+                             prefs.show_right_ruler = false;
+                         }
+                         Some(Action::ToggleBorderRulers) => {
+                             let show = !(prefs.show_bottom_ruler || prefs.show_right_ruler);
+                             prefs.show_bottom_ruler = show;
+                             prefs.show_right_ruler = show;
+                         }
+                         Some(Action::ToggleBottomRuler) => {
+                             prefs.show_bottom_ruler = !prefs.show_bottom_ruler;
+                         }
+                         Some(Action::ToggleRightRuler) => {
+                             prefs.show_right_ruler = !prefs.show_right_ruler;
+                         }
+                         Some(Action::ToggleXRuler) => {
+                             prefs.show_x_ruler = !prefs.show_x_ruler;
+                         }
+                         Some(Action::SwapButtons) => {
+                             brush.swap();
+                         }
+                         Some(action @ (Action::GrowBrush | Action::ShrinkBrush)) => {
+                             brush.resize(action == Action::GrowBrush);
+                         }
+                         Some(Action::ToggleYRuler) => {
+                             prefs.show_y_ruler = !prefs.show_y_ruler;
+                         }
+                         Some(Action::ToggleHalfBlock) => {
+                             prefs.half_block = !prefs.half_block;
+                         }
+                         Some(Action::BucketFill) => {
+                             if let Some(cell) = hover {
+                                 let tolerance = prefs.fill_tolerance;
+                                 let message =
+                                     bucket_fill(&mut map, &locked, cell, &brush, tolerance);
+                                 status_message = Some(message);
+                             }
+                         }
+                         Some(action @ (Action::ClearMap | Action::FillMap)) => {
+                             status_message = Some(if action == Action::ClearMap {
+                                 format!("cleared {} cells to water", clear_map(&mut map, &locked))
+                             } else {
+                                 let filled = fill_map(&mut map, &locked, 1.);
+                                 format!("filled {} cells with land", filled)
+                             });
+                         }
+                         Some(Action::ToggleTextured) => {
+                             prefs.textured = !prefs.textured;
+                         }
+                         Some(Action::ToggleHeatmap) => {
+                             prefs.heatmap = !prefs.heatmap;
+                         }
+                         Some(Action::ToggleElevation) => {
+                             prefs.elevation = !prefs.elevation;
+                         }
+                         Some(Action::ToggleGrid) => {
+                             prefs.grid = !prefs.grid;
+                         }
+                         Some(Action::ToggleCursor) => {
+                             cursor = match cursor {
+                                 Some(_) => None,
+                                 None => Some(hover.unwrap_or((
+                                     (camera_x + view_width / 2).min(map_size.width - 1),
+                                     (camera_y + view_height / 2).min(map_size.height - 1),
+                                 ))),
+                             };
+                         }
+                         Some(
+                             action @ (Action::MoveLeft
+                             | Action::MoveRight
+                             | Action::MoveUp
+                             | Action::MoveDown),
+                         ) if cursor.is_some() => {
+                             let step = zoom.saturating_mul(count.unwrap_or(1)) as isize;
+                             let (dx, dy) = match action {
+                                 Action::MoveLeft => (-step, 0),
+                                 Action::MoveRight => (step, 0),
+                                 Action::MoveUp => (0, -step),
+                                 _ => (0, step),
+                             };
+                             let (x, y) = cursor.unwrap();
+                             let moved = (
+                                 x.saturating_add_signed(dx).min(map_size.width - 1),
+                                 y.saturating_add_signed(dy).min(map_size.height - 1),
+                             );
+                             edge = moved == (x, y);
+                             cursor = Some(moved);
+                             let view = (view_width, view_height);
+                             (camera_x, camera_y) =
+                                 scroll_to_show(moved, (camera_x, camera_y), view);
+                         }
+                         // Like the two mouse buttons
+                         Some(action @ (Action::DrawAtCursor | Action::EraseAtCursor)) => {
+                             if let Some((x, y)) = cursor {
+                                 let value = match action {
+                                     Action::DrawAtCursor if brush.walls => WALL,
+                                     Action::DrawAtCursor => brush.left,
+                                     _ => brush.right,
+                                 };
+                                 let water_level = presets[current_preset].water_level;
+                                 paint_brush(&mut map, &locked, x, y, value, &brush, water_level);
+                             }
+                         }
+                         Some(Action::MoveLeft) => {
+                             let step = zoom.saturating_mul(count.unwrap_or(1));
+                             edge = camera_x == 0;
+                             camera_x = camera_x.saturating_sub(step);
+                         }
+                         Some(Action::MoveRight) => {
+                             let step = zoom.saturating_mul(count.unwrap_or(1));
+                             edge = camera_x >= width;
+                             camera_x = (camera_x + step).min(width);
+                         }
+                         Some(Action::MoveUp) => {
+                             let step = zoom.saturating_mul(count.unwrap_or(1));
+                             edge = camera_y == 0;
+                             camera_y = camera_y.saturating_sub(step);
+                         }
+                         Some(Action::MoveDown) => {
+                             let step = zoom.saturating_mul(count.unwrap_or(1));
+                             edge = camera_y >= height;
+                             camera_y = (camera_y + step).min(height);
+                         }
+                         Some(Action::ZoomIn) => {
+                             fit_to_screen = false;
+                             prefs.zoom = zoom.saturating_sub(1).max(1);
+                         }
+                         Some(Action::ZoomOut) => {
+                             fit_to_screen = false;
+                             prefs.zoom = (zoom + 1).min(map_size.width.max(map_size.height));
+                         }
+                         Some(Action::Fit) => {
+                             fit_to_screen = !fit_to_screen;
+                             if !fit_to_screen {
+                                 prefs.zoom = 1;
+                             }
+                         }
+                         Some(Action::ToggleLock) => {
+                             if let Some((x, y)) = cursor.or(hover) {
+                                 locked[y][x] = !locked[y][x];
+                                 status_message = Some(if locked[y][x] {
+                                     format!("locked ({}, {})", x, viewport.display_y(y))
+                                 } else {
+                                     format!("unlocked ({}, {})", x, viewport.display_y(y))
+                                 });
+                             }
+                         }
+                         Some(Action::TogglePin) => {
+                             if let Some(cell) = hover {
+                                 match pins.iter().position(|&pin| pin == cell) {
+                                     Some(i) => {
+                                         pins.remove(i);
+                                     }
+                                     None => pins.push(cell),
+                                 }
+                             }
+                         }
+                         Some(action @ (Action::NextPin | Action::PreviousPin))
+                             if !pins.is_empty() =>
+                         {
+                             let count = pins.len();
+                             let i = match (current_pin, action) {
+                                 (None, Action::NextPin) => 0,
+                                 (None, _) => count - 1,
+                                 (Some(i), Action::NextPin) => (i + 1) % count,
+                                 // Jumping back from a removed last pin
+                                 (Some(i), _) => (i.min(count) + count - 1) % count,
+                             };
+                             current_pin = Some(i);
+                             let (x, y) = pins[i];
+                             camera_x = x.saturating_sub(view_width / 2).min(width);
+                             camera_y = y.saturating_sub(view_height / 2).min(height);
+                             status_message = Some(format!("pin {}/{}", i + 1, count));
+                         }
+                         Some(Action::CenterOnLand) => {
+                             let water_level = presets[current_preset].water_level;
+                             if let Some((x, y)) = land_centroid(&map, water_level) {
+                                 camera_x = x.saturating_sub(view_width / 2).min(width);
+                                 camera_y = y.saturating_sub(view_height / 2).min(height);
+                             }
+                         }
+                         Some(Action::PageDown) => {
+                             edge = camera_y >= height;
+                             camera_y = (camera_y + view_height).min(height);
+                             camera_y = snap_row(camera_y, true, &viewport, &prefs);
+                         }
+                         Some(Action::PageUp) => {
+                             edge = camera_y == 0;
+                             camera_y = camera_y.saturating_sub(view_height);
+                             camera_y = snap_row(camera_y, false, &viewport, &prefs);
+                         }
+                         Some(Action::LeftEdge) => {
+                             camera_x = 0;
+                         }
+                         Some(Action::RightEdge) => {
+                             camera_x = width;
+                         }
+                         Some(Action::Top) if prefix == Some('g') => {
+                             camera_y = 0;
+                         }
+                         Some(Action::Top) => pending_key = Some(('g', Instant::now())),
+                         Some(Action::Bottom) => {
+                             camera_y = height;
+                         }
+                         Some(Action::TopLeft) => {
+                             camera_x = 0;
+                             camera_y = 0;
+                         }
+                         Some(Action::BottomRight) => {
+                             camera_x = width;
+                             camera_y = height;
+                         }
+                         Some(Action::ToggleHud) => {
+                             show_hud = !show_hud;
+                         }
+                         Some(Action::ResetView) if prefix == Some('z') => {
+                             prefs.reset_view();
+                             camera_x = 0;
+                             camera_y = 0;
+                             fit_to_screen = false;
+                             show_hud = false;
+                             status_message = Some("view reset".to_string());
+                         }
+                         Some(Action::ResetView) => pending_key = Some(('z', Instant::now())),
+                         Some(Action::ToggleHelp) => {
+                             prefs.show_help = !prefs.show_help;
+                         }
+                         Some(Action::ToggleStats) => {
+                             stats = match stats {
+                                 Some(_) => None,
+                                 None => Some(stats::map_statistics(
+                                     &map,
+                                     presets[current_preset].water_level,
+                                     &prefs.tiers,
+                                 )),
+                             };
+                         }
+                         Some(Action::CycleStamp) => {
+                             selected_stamp = match selected_stamp {
+                                 None if !stamps.is_empty() => Some(0),
+                                 Some(i) if i + 1 < stamps.len() => Some(i + 1),
+                                 _ => None,
+                             };
+                             match selected_stamp {
+                                 Some(i) => log::info!("stamp: {}", stamps[i].name),
+                                 None => log::info!("stamp: none"),
+                             }
+                         }
+                         Some(Action::SaveStamp) => {
+                             let region = stamp::copy_region(
+                                 &map,
+                                 camera_x,
+                                 camera_y,
+                                 view_width,
+                                 view_height,
+                             );
+                             match stamp::save_stamp_in_dir(&region, &stamps_dir) {
+                                 Ok(path) => match stamp::load_stamp(&path) {
+                                     Ok(new_stamp) => {
+                                         log::info!("saved stamp {}", path.display());
+                                         stamps.push(new_stamp);
+                                     }
+                                     Err(err) => log::warn!("failed to reload stamp: {}", err),
+                                 },
+                                 Err(err) => log::warn!("failed to save stamp: {}", err),
+                             }
+                         }
+                         Some(Action::Annotate) => {
+                             if let Some((x, y)) = hover {
+                                 let text = annotations.get(&(x, y)).cloned().unwrap_or_default();
+                                 let shown_y = viewport.display_y(y);
+                                 let title = format!("Note at {}, {} (empty removes)", x, shown_y);
+                                 let action = PromptAction::Annotate(x, y);
+                                 prompt = Some((Prompt::new(title, text), action));
+                             }
+                         }
+                         Some(Action::RemoveNote) => {
+                             if let Some((x, y)) = hover {
+                                 annotations.remove(&(x, y));
+                             }
+                         }
+                         Some(Action::TogglePause) => {
+                             game_state.togle_pause();
+                         }
+                         Some(Action::ToggleSmoothBrush) => {
+                             brush.smooth = !brush.smooth;
+                         }
+                         Some(Action::ToggleWalls) => {
+                             brush.walls = !brush.walls;
+                         }
+                         Some(Action::CycleMask) => {
+                             brush.mask = PaintMask::cycle(brush.mask);
+                         }
+                         Some(Action::ToggleLasso) => {
+                             lasso_mode = !lasso_mode;
+                             lasso = None;
+                             shape_tool = None;
+                             shape_anchor = None;
+                         }
+                         Some(action @ (Action::ToggleLineTool | Action::ToggleRectTool)) => {
+                             let shape = if action == Action::ToggleLineTool {
+                                 Shape::Line
+                             } else {
+                                 Shape::Rect
+                             };
+                             shape_tool = (shape_tool != Some(shape)).then_some(shape);
+                             shape_anchor = None;
+                             lasso_mode = false;
+                             lasso = None;
+                             status_message = shape_tool
+                                 .map(|shape| format!("{}: click where it starts", shape.name()));
+                         }
+                         Some(Action::ToggleInspector) => {
+                             prefs.show_inspector = !prefs.show_inspector;
+                         }
+                         Some(Action::ToggleMinimap) => {
+                             prefs.show_minimap = !prefs.show_minimap;
+                         }
+                         Some(Action::NextPreset) if background.is_some() => {
+                             status_message = Some(BUSY.into());
+                         }
+                         Some(Action::NextPreset) => {
+                             current_preset = (current_preset + 1) % presets.len();
+                             let preset = &presets[current_preset];
+                             background = Some(apply_preset(&map, preset, &mut noise));
+                             status_message = Some(format!("preset {}", preset.name));
+                         }
+                         Some(action @ (Action::RaiseSeaLevel | Action::LowerSeaLevel)) => {
+                             // Counted in steps so repeated presses land on round values
+                             let preset = &mut presets[current_preset];
+                             let steps = (preset.water_level / SEA_LEVEL_STEP).round();
+                             let step = if action == Action::RaiseSeaLevel { 1. } else { -1. };
+                             preset.water_level = ((steps + step) * SEA_LEVEL_STEP).clamp(-1., 1.);
+                         }
+                         _ => {}
+                     },
+                     Event::Mouse(mouse_event) => {
+                         hover = shown.screen_to_map(mouse_event.column, mouse_event.row);
+
+                         let ctrl = mouse_event.modifiers.contains(KeyModifiers::CONTROL);
+                         let shift = mouse_event.modifiers.contains(KeyModifiers::SHIFT);
+                         let pan = zoom * WHEEL_PAN;
+                         match mouse_event.kind {
+                             MouseEventKind::ScrollUp if ctrl => brush.step_left(1.),
+                             MouseEventKind::ScrollDown if ctrl => brush.step_left(-1.),
+                             // Shift turns the wheel sideways where the terminal does not
+                             MouseEventKind::ScrollUp if shift => {
+                                 camera_x = camera_x.saturating_sub(pan);
+                             }
+                             MouseEventKind::ScrollDown if shift => {
+                                 camera_x = (camera_x + pan).min(width);
+                             }
+                             MouseEventKind::ScrollUp => camera_y = camera_y.saturating_sub(pan),
+                             MouseEventKind::ScrollDown => camera_y = (camera_y + pan).min(height),
+                             MouseEventKind::ScrollLeft => camera_x = camera_x.saturating_sub(pan),
+                             MouseEventKind::ScrollRight => camera_x = (camera_x + pan).min(width),
+                             _ => {}
+                         }
+
+                         let painting = match mouse_event.kind {
+                             MouseEventKind::Down(button) => {
+                                 stroke = true;
+                                 Some(button)
+                             }
+                             // A drag writes each cell it crosses once
+                             MouseEventKind::Drag(button) if hover != last_painted => Some(button),
+                             MouseEventKind::Up(_) => {
+                                 last_painted = None;
+                                 stroke = false;
+                                 None
+                             }
+                             _ => None,
+                         };
+                         let dragging = matches!(mouse_event.kind, MouseEventKind::Drag(_));
+
+                         // A drag against the edge of the map area scrolls towards it
+                         if dragging && prefs.edge_pan {
+                             let area = shown.area;
+                             let (col, row) = (mouse_event.column, mouse_event.row);
+                             if col <= area.x {
+                                 camera_x = camera_x.saturating_sub(zoom);
+                             } else if col + 1 >= area.right() {
+                                 camera_x = (camera_x + zoom).min(width);
+                             }
+                             if row <= area.y {
+                                 camera_y = camera_y.saturating_sub(zoom);
+                             } else if row + 1 >= area.bottom() {
+                                 camera_y = (camera_y + zoom).min(height);
+                             }
+                         }
+
+                         if game_state == GameState::Draw && lasso_mode {
+                             match (mouse_event.kind, hover) {
+                                 (MouseEventKind::Down(MouseButton::Left), Some((x, y))) => {
+                                     lasso = Some(Lasso::new(x, y));
+                                 }
+                                 (MouseEventKind::Drag(MouseButton::Left), Some((x, y))) => {
+                                     if let Some(current) = &mut lasso {
+                                         current.push(x, y);
+                                     }
+                                 }
+                                 (MouseEventKind::Up(MouseButton::Left), _) => {
+                                     if let Some(current) = &mut lasso {
+                                         current.close(map_size);
+                                     }
+                                 }
+                                 _ => {}
+                             }
+                         } else if let (GameState::Draw, Some(shape)) = (&game_state, shape_tool) {
+                             match (mouse_event.kind, hover) {
+                                 (MouseEventKind::Down(MouseButton::Left), Some(cell)) => {
+                                     if let Some(start) = shape_anchor.take() {
+                                         let value = if brush.walls { WALL } else { brush.left };
+                                         let water_level = presets[current_preset].water_level;
+                                         let painted = match shape {
+                                             Shape::Line => draw_line(
+                                                 &mut map,
+                                                 &locked,
+                                                 start,
+                                                 cell,
+                                                 value,
+                                                 &brush,
+                                                 water_level,
+                                             ),
+                                             // Ctrl on the second corner fills the box
+                                             Shape::Rect => fill::draw_rect(
+                                                 &mut map, &locked, start, cell, value, ctrl,
+                                             ),
+                                         };
+                                         let message =
+                                             format!("{} of {} cells", shape.name(), painted);
+                                         status_message = Some(message);
+                                     } else {
+                                         shape_anchor = Some(cell);
+                                         let hint = match shape {
+                                             Shape::Line => "click the end",
+                                             Shape::Rect => "click the other corner, ctrl fills",
+                                         };
+                                         let message = format!(
+                                             "{}: {}, right click cancels",
+                                             shape.name(),
+                                             hint
+                                         );
+                                         status_message = Some(message);
+                                     }
+                                 }
+                                 (MouseEventKind::Down(MouseButton::Right), _) => {
+                                     if shape_anchor.take().is_some() {
+                                         let message = format!("{} cancelled", shape.name());
+                                         status_message = Some(message);
+                                     }
+                                 }
+                                 _ => {}
+                             }
+                         } else if game_state == GameState::Draw {
+                             if let Some(button) = painting {
+                                 // Fast drags skip cells between polls, so join them up
+                                 let cells: Vec<(usize, usize)> = match (last_painted, hover) {
+                                     (Some(from), Some(to)) if dragging => {
+                                         line_cells(from, to).into_iter().skip(1).collect()
+                                     }
+                                     _ => hover.into_iter().collect(),
+                                 };
+                                 last_painted = hover;
+                                 match button {
+                                     // Stamps go down once per click, not along a drag
+                                     MouseButton::Left if selected_stamp.is_some() && dragging => {}
+                                     MouseButton::Left if selected_stamp.is_some() => {
+                                         if let Some((map_x, map_y)) = hover {
+                                             let stamp = &stamps[selected_stamp.unwrap()];
+                                             let placed = stamp::place_stamp(
+                                                 &mut map, &locked, stamp, map_x, map_y,
+                                             );
+                                             if !placed {
+                                                 status_message =
+                                                     Some("stamp clipped at map edge".to_string());
+                                             }
+                                         }
+                                     }
+                                     MouseButton::Left if brush.smooth => {
+                                         let water_level = presets[current_preset].water_level;
+                                         for &(map_x, map_y) in &cells {
+                                             smooth_around(
+                                                 &mut map,
+                                                 &locked,
+                                                 map_x,
+                                                 map_y,
+                                                 &brush,
+                                                 water_level,
+                                             );
+                                         }
+                                     }
+                                     MouseButton::Left if brush.walls => {
+                                         let water_level = presets[current_preset].water_level;
+                                         for &(map_x, map_y) in &cells {
+                                             paint_brush(
+                                                 &mut map,
+                                                 &locked,
+                                                 map_x,
+                                                 map_y,
+                                                 WALL,
+                                                 &brush,
+                                                 water_level,
+                                             );
+                                         }
+                                     }
+                                     MouseButton::Left => {
+                                         handle_left_click(
+                                             &cells,
+                                             &mut map,
+                                             &locked,
+                                             &brush,
+                                             presets[current_preset].water_level,
+                                         );
+                                     }
+                                     MouseButton::Middle if dragging => {}
+                                     MouseButton::Middle => {
+                                         if let Some(cell) = hover {
+                                             let tolerance = prefs.fill_tolerance;
+                                             let message = bucket_fill(
+                                                 &mut map, &locked, cell, &brush, tolerance,
+                                             );
+                                             status_message = Some(message);
+                                         }
+                                     }
+                                     MouseButton::Right => {
+                                         handle_right_click(
+                                             &cells,
+                                             &mut map,
+                                             &locked,
+                                             &brush,
+                                             presets[current_preset].water_level,
+                                         );
+                                     }
+                                 }
+                             }
+                         }
+                     },
+                     _ => {}
+                 }
+             }
+
+             if edge {
+                 flash = true;
+                 status_message = Some("at map edge".to_string());
+             }
+        }
+
+        
+        let frame_elapsed = Instant::now().duration_since(now);
+        if frame_elapsed < FRAME_DURATION {
+            std::thread::sleep(FRAME_DURATION - frame_elapsed);
+        }
+    }
+
+    restore_terminal(mouse)?;
+
+    if let Err(err) = prefs::save_prefs(&prefs, &prefs_path) {
+        log::warn!("failed to save prefs {}: {}", prefs_path.display(), err);
+    }
+    if prefs.save_history {
+        if let Err(err) = history::save_history(&history, &history_path) {
+            log::warn!("failed to save history {}: {}", history_path.display(), err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Log file and level, `GAMELIVE_LOG` and `GAMELIVE_LOG_LEVEL` overriding
+/// the defaults. At level `off` no file is created.
+fn init_logger() -> io::Result<()> {
+    let path = std::env::var_os("GAMELIVE_LOG").unwrap_or_else(|| LOG_FILE.into());
+    let level = match std::env::var("GAMELIVE_LOG_LEVEL") {
+        Ok(name) => name.parse().map_err(|_| {
+            let message = format!("unknown log level '{}' in GAMELIVE_LOG_LEVEL", name);
+            io::Error::new(io::ErrorKind::InvalidInput, message)
+        })?,
+        Err(_) => LevelFilter::Debug,
+    };
+    if level == LevelFilter::Off {
+        return Ok(());
+    }
+
+    let path = Path::new(&path);
+    let file = File::create(path).map_err(|err| {
+        let message = format!("failed to create log file {}: {}", path.display(), err);
+        io::Error::new(err.kind(), message)
+    })?;
+    CombinedLogger::init(vec![WriteLogger::new(level, Config::default(), file)])
+        .map_err(io::Error::other)
+}
+
+/// Raw mode, the alternate screen with the shell's title saved, and mouse
+/// capture where the terminal allows it, which the second value tells.
+fn setup_terminal() -> io::Result<(Tui, bool)> {
+    enable_raw_mode()?;
+    crossterm::execute!(
+        io::stdout(),
+        crossterm::terminal::EnterAlternateScreen,
+        Print(PUSH_TITLE)
+    )?;
+    // Some terminals refuse mouse reporting; the keyboard tools still work
+    let mouse = match crossterm::execute!(io::stdout(), EnableMouseCapture) {
+        Ok(()) => true,
+        Err(err) => {
+            log::warn!("mouse capture unavailable, continuing without mouse: {}", err);
+            false
+        }
+    };
+
+    let terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    Ok((terminal, mouse))
+}
+
+/// Undoes `setup_terminal`: raw mode, mouse capture, the alternate screen
+/// and the title.
+fn restore_terminal(mouse: bool) -> io::Result<()> {
+    disable_raw_mode()?;
+    if mouse {
+        crossterm::execute!(io::stdout(), DisableMouseCapture)?;
+    }
+    crossterm::execute!(io::stdout(), crossterm::terminal::LeaveAlternateScreen, Print(POP_TITLE))
+}
+
+/// Hands the terminal back to the shell and stops the process, as the default
+/// SIGTSTP action would, then takes the terminal over again once continued.
+#[cfg(unix)]
+fn suspend(terminal: &mut Tui, mouse: bool) -> io::Result<()> {
+    restore_terminal(mouse)?;
+
+    signal_hook::low_level::emulate_default_handler(signal_hook::consts::SIGTSTP)?;
+
+    // Resumed by SIGCONT
+    enable_raw_mode()?;
+    crossterm::execute!(
+        io::stdout(),
+        crossterm::terminal::EnterAlternateScreen,
+        Print(PUSH_TITLE)
+    )?;
+    if mouse {
+        crossterm::execute!(io::stdout(), EnableMouseCapture)?;
+    }
+    terminal.clear()
+}
+
+/// File browser for commands that were given no file.
+fn browser_for(command: &Command) -> Option<(FileBrowser, FileAction)> {
+    let cwd = std::path::Path::new(".");
+    match command {
+        Command::Edit(Target::Browse) => Some((
+            FileBrowser::new("Load map", cwd, &["json", "ron", "gml"], false),
+            FileAction::Load,
+        )),
+        Command::Write(Target::Browse) => Some((
+            FileBrowser::new("Save map", cwd, &["json", "ron", "gml"], true),
+            FileAction::Save,
+        )),
+        Command::Export(None) => Some((
+            FileBrowser::new("Export map", cwd, &["png", "pgm", "txt"], true),
+            FileAction::Export,
+        )),
+        Command::Import(None) => Some((
+            FileBrowser::new("Import map", cwd, &["txt"], false),
+            FileAction::Import,
+        )),
+        _ => None,
+    }
+}
+
+/// Runs a `:` command other than quit, returning the message for the status
+/// bar.
+fn run_command(
+    command: Command,
+    map: &mut Vec<Vec<f64>>,
+    locked: &mut Locks,
+    annotations: &mut Annotations,
+    document: &mut Document,
+    prefs: &mut Prefs,
+    water_level: f64,
+) -> Result<String, String> {
+    // Slots are the editor's own files and always stay row-major
+    let transpose = |target: &Target| matches!(target, Target::Path(_)) && prefs.transpose_files;
+    let target_path = |target: &Target| match target {
+        Target::Browse => None,
+        Target::Slot(slot) => Some(save::slot_path(*slot)),
+        Target::Path(path) => Some(path.clone()),
+    };
+    let slot = |target: &Target| match target {
+        Target::Slot(slot) => Some(*slot),
+        _ => None,
+    };
+
+    Ok(match command {
+        Command::Write(target) => {
+            let Some(path) = target_path(&target) else {
+                return Err("no file given".to_string());
+            };
+            match save::save_map(map, locked, annotations, &path, transpose(&target)) {
+                Ok(()) => {
+                    document.saved(&path, slot(&target), map, locked);
+                    format!("wrote {}", path.display())
+                }
+                Err(err) => return Err(format!("failed to write {}: {}", path.display(), err)),
+            }
+        }
+        Command::Edit(target) => {
+            let Some(path) = target_path(&target) else {
+                return Err("no file given".to_string());
+            };
+            match save::load_map(&path, transpose(&target), Some(MapSize::of(map))) {
+                Ok((loaded, loaded_locks, loaded_notes)) => {
+                    *map = loaded;
+                    *locked = loaded_locks;
+                    *annotations = loaded_notes;
+                    document.saved(&path, slot(&target), map, locked);
+                    format!("loaded {}", path.display())
+                }
+                Err(err) => return Err(format!("failed to load {}: {}", path.display(), err)),
+            }
+        }
+        Command::Export(None) => return Err("no file given".to_string()),
+        Command::Export(Some(path)) => {
+            let result = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("png") => export::export_png(map, &path),
+                Some("pgm") => export::export_pgm(map, &path),
+                Some("txt") => {
+                    let region = MapSize::of(map).region();
+                    export::export_ascii(map, region, &path, prefs.transpose_files, water_level)
+                }
+                _ => {
+                    let path = path.display();
+                    return Err(format!("cannot export {}: use .png, .pgm or .txt", path));
+                }
+            };
+            match result {
+                Ok(()) => {
+                    log::info!("exported {}", path.display());
+                    format!("exported {}", path.display())
+                }
+                Err(err) => return Err(format!("failed to export {}: {}", path.display(), err)),
+            }
+        }
+        Command::Import(None) => return Err("no file given".to_string()),
+        Command::Import(Some(path)) => {
+            // Transposed text has a line per column
+            let size = MapSize::of(map);
+            let imported = if prefs.transpose_files {
+                let columns = MapSize {
+                    width: size.height,
+                    height: size.width,
+                };
+                export::import_ascii_file(&path, columns).map(|map| save::transpose(&map))
+            } else {
+                export::import_ascii_file(&path, size)
+            };
+            match imported {
+                Ok(imported) => {
+                    *map = imported;
+                    *locked = no_locks(size);
+                    annotations.clear();
+                    format!("imported {}", path.display())
+                }
+                Err(err) => return Err(format!("failed to import {}: {}", path.display(), err)),
+            }
+        }
+        Command::Fill => format!("filled {} cells with land", fill_map(map, locked, 1.)),
+        Command::Transpose => {
+            prefs.transpose_files = !prefs.transpose_files;
+            if prefs.transpose_files {
+                "map files are now column-major".to_string()
+            } else {
+                "map files are now row-major".to_string()
+            }
+        }
+        Command::XTick(tick) => {
+            prefs.x_tick = tick;
+            format!("X ruler labels every {} cells", tick)
+        }
+        Command::YTick(tick) => {
+            prefs.y_tick = tick;
+            format!("Y ruler labels every {} cells", tick)
+        }
+        Command::Tolerance(tolerance) => {
+            prefs.fill_tolerance = tolerance;
+            format!("fill tolerance {}", tolerance)
+        }
+        Command::Snap => {
+            prefs.snap_pages = !prefs.snap_pages;
+            if prefs.snap_pages {
+                "page jumps snap to ruler labels".to_string()
+            } else {
+                "page jumps no longer snap".to_string()
+            }
+        }
+        Command::EdgePan => {
+            prefs.edge_pan = !prefs.edge_pan;
+            if prefs.edge_pan {
+                "drags at the map edge scroll".to_string()
+            } else {
+                "drags no longer scroll".to_string()
+            }
+        }
+        Command::Smooth => {
+            prefs.smooth_pan = !prefs.smooth_pan;
+            if prefs.smooth_pan {
+                "smooth panning on".to_string()
+            } else {
+                "smooth panning off".to_string()
+            }
+        }
+        Command::Origin => {
+            prefs.origin_bottom = !prefs.origin_bottom;
+            if prefs.origin_bottom {
+                "rows now count up from the bottom".to_string()
+            } else {
+                "rows now count down from the top".to_string()
+            }
+        }
+        Command::Preset(_)
+        | Command::Theme(_)
+        | Command::Diff(_)
+        | Command::Seed(_)
+        | Command::Regen
+        | Command::Erode { .. }
+        | Command::Rivers(_)
+        | Command::Quit => String::new(),
+    })
+}
+
+/// Stores `cells` as the "selection" stamp, replacing the previous copy, and
+/// returns its index.
+fn keep_copy(stamps: &mut Vec<stamp::Stamp>, cells: Vec<Vec<f64>>) -> usize {
+    let copied = stamp::Stamp {
+        name: "selection".to_string(),
+        cells,
+    };
+    match stamps.iter().position(|s| s.name == copied.name) {
+        Some(i) => {
+            stamps[i] = copied;
+            i
+        }
+        None => {
+            stamps.push(copied);
+            stamps.len() - 1
+        }
+    }
+}
+
+/// Splits the bottom status bar row off `area`.
+fn split_status_bar(area: Rect) -> (Rect, Rect) {
+    let status_height = STATUS_BAR_SIZE.min(area.height);
+    let main_area = Rect {
+        height: area.height - status_height,
+        ..area
+    };
+    let status_area = Rect {
+        y: area.y + main_area.height,
+        height: status_height,
+        ..area
+    };
+
+    (main_area, status_area)
+}
+
+/// `hover` is the cell under the mouse with its value and note, if any, and
+/// `ticks` the cells between X and Y ruler labels.
+#[allow(clippy::too_many_arguments)]
+fn status_line(
+    hover: Option<((usize, usize), f64, Option<&str>)>,
+    viewport: &Viewport,
+    ticks: (usize, usize),
+    selection: Option<&Selection>,
+    lasso: Option<Option<&Lasso>>,
+    brush: &Brush,
+    last_slot: Option<u8>,
+    message: Option<&str>,
+) -> Paragraph<'static> {
+    let mut parts = Vec::new();
+
+    let left = if brush.smooth {
+        "smooth".to_string()
+    } else if brush.walls {
+        "wall".to_string()
+    } else {
+        brush.left.to_string()
+    };
+    parts.push(format!("L {} R {}", left, brush.right));
+    let (camera_x, camera_y) = (viewport.camera_x, viewport.camera_y);
+    parts.push(format!("view {}, {}", camera_x, viewport.display_y(camera_y)));
+    parts.push(format!("ticks {}, {}", ticks.0, ticks.1));
+    if let Some(((x, y), value, _)) = hover {
+        let value = if is_transparent(value) {
+            "missing".to_string()
+        } else {
+            format!("{:.2}", value)
+        };
+        parts.push(format!("{}, {} = {}", x, viewport.display_y(y), value));
+    }
+    if brush.size > 1 {
+        parts.push(format!("brush {0}x{0}", brush.size));
+    }
+    if let Some(mask) = brush.mask {
+        parts.push(format!("mask {}", mask.name()));
+    }
+    if let Some(selection) = selection {
+        let (x, y, width, height) = selection.rect();
+        let y = viewport.display_y(y);
+        parts.push(format!(
+            "select {}x{} at {}, {} (y/f/d/r/o, Enter/Bksp paint, Esc)",
+            width, height, x, y
+        ));
+    }
+    match lasso {
+        Some(Some(lasso)) if lasso.is_closed() => {
+            let (_, _, width, height) = lasso.bounds();
+            parts.push(format!("lasso {}x{} (y/f/d/o, Esc)", width, height));
+        }
+        Some(_) => parts.push("lasso".to_string()),
+        None => {}
+    }
+
+    if let Some(slot) = last_slot {
+        parts.push(format!("slot {}", slot));
+    }
+    if let Some((_, _, Some(note))) = hover {
+        parts.push(format!("note: {}", note));
+    }
+    if let Some(message) = message {
+        parts.push(message.to_string());
+    }
+
+    Paragraph::new(parts.join(" | "))
+}
+
+/// Returns the previous value of the cell, or `None` when nothing was written
+/// because the cell is off the map or `value` is transparent.
+fn draw_on_map(
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    map_x: usize,
+    map_y: usize,
+    value: f64,
+) -> Option<f64> {
+   let on_map = MapSize::of(map).contains(map_x, map_y);
+   if on_map && !is_transparent(value) && !locked[map_y][map_x] {
+       Some(std::mem::replace(&mut map[map_y][map_x], value))
+   } else {
+       None
+   }
+}
+
+/// Draws `value` unless the brush's mask rejects what the cell holds now.
+/// Shared by the mouse buttons and their keyboard equivalents.
+fn paint_cell(
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    map_x: usize,
+    map_y: usize,
+    value: f64,
+    brush: &Brush,
+    water_level: f64,
+) -> Option<f64> {
+    if !brush.allows(*map.get(map_y)?.get(map_x)?, water_level) {
+        return None;
+    }
+    draw_on_map(map, locked, map_x, map_y, value)
+}
+
+/// Sets each cell of the 3×3 block around `(x, y)` to the mean of its own
+/// neighbourhood, through `paint_cell` so masks and locks still apply. Walls
+/// and missing cells neither change nor count towards a mean.
+fn smooth_around(
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    x: usize,
+    y: usize,
+    brush: &Brush,
+    water_level: f64,
+) {
+    let terrain = |x: usize, y: usize| {
+        let value = *map.get(y)?.get(x)?;
+        (!is_transparent(value) && !is_wall(value)).then_some(value)
+    };
+    let around = |x: usize, y: usize| {
+        let ys = y.saturating_sub(1)..=y + 1;
+        ys.flat_map(move |y| (x.saturating_sub(1)..=x + 1).map(move |x| (x, y)))
+    };
+
+    // Means come from the values before any of the block was rewritten
+    let smoothed: Vec<(usize, usize, f64)> = around(x, y)
+        .filter(|&(x, y)| terrain(x, y).is_some())
+        .map(|(x, y)| {
+            let values: Vec<f64> = around(x, y).filter_map(|(x, y)| terrain(x, y)).collect();
+            (x, y, values.iter().sum::<f64>() / values.len() as f64)
+        })
+        .collect();
+    for (x, y, value) in smoothed {
+        paint_cell(map, locked, x, y, value, brush, water_level);
+    }
+}
+
+/// Paints the `brush.size` square centred on `(map_x, map_y)`, leaning
+/// towards the bottom right for even sizes and clipped at the map edges.
+/// Returns how many cells were written.
+fn paint_brush(
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    map_x: usize,
+    map_y: usize,
+    value: f64,
+    brush: &Brush,
+    water_level: f64,
+) -> usize {
+    let size = brush.size as isize;
+    let offsets = -(size - 1) / 2..size - (size - 1) / 2;
+    let mut painted = 0;
+    for dy in offsets.clone() {
+        for dx in offsets.clone() {
+            let (Some(x), Some(y)) = (map_x.checked_add_signed(dx), map_y.checked_add_signed(dy))
+            else {
+                continue;
+            };
+            painted += paint_cell(map, locked, x, y, value, brush, water_level).is_some() as usize;
+        }
+    }
+    painted
+}
+
+/// Every cell on the straight line from `from` to `to`, both ends included,
+/// stepping one cell at a time as Bresenham's algorithm does.
+fn line_cells(from: (usize, usize), to: (usize, usize)) -> Vec<(usize, usize)> {
+    let (mut x, mut y) = (from.0 as isize, from.1 as isize);
+    let (end_x, end_y) = (to.0 as isize, to.1 as isize);
+    let (dx, dy) = ((end_x - x).abs(), -(end_y - y).abs());
+    let (step_x, step_y) = ((end_x - x).signum(), (end_y - y).signum());
+    let mut error = dx + dy;
+    let mut cells = vec![(x as usize, y as usize)];
+    while (x, y) != (end_x, end_y) {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        cells.push((x as usize, y as usize));
+    }
+    cells
+}
+
+/// Paints the brush along the straight line from `from` to `to`, both ends
+/// included. Returns how many cells were written.
+fn draw_line(
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    from: (usize, usize),
+    to: (usize, usize),
+    value: f64,
+    brush: &Brush,
+    water_level: f64,
+) -> usize {
+    let mut painted = 0;
+    for (x, y) in line_cells(from, to) {
+        painted += paint_brush(map, locked, x, y, value, brush, water_level);
+    }
+    painted
+}
+
+/// Sets every unlocked cell to `value`, returning how many changed.
+fn fill_map(map: &mut [Vec<f64>], locked: &Locks, value: f64) -> usize {
+    let mut changed = 0;
+    for (row, locked) in map.iter_mut().zip(locked) {
+        for (cell, _) in row.iter_mut().zip(locked).filter(|(_, &locked)| !locked) {
+            // Bit for bit, so holes filled with a hole do not count
+            changed += (cell.to_bits() != value.to_bits()) as usize;
+            *cell = value;
+        }
+    }
+    changed
+}
+
+/// Floods every unlocked cell to `WATER_LEVEL`.
+fn clear_map(map: &mut [Vec<f64>], locked: &Locks) -> usize {
+    fill_map(map, locked, WATER_LEVEL)
+}
+
+/// Fills from `cell` with what the left button draws, as the `b` key and
+/// the middle button do.
+fn bucket_fill(
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    cell: (usize, usize),
+    brush: &Brush,
+    tolerance: f64,
+) -> String {
+    let value = if brush.walls { WALL } else { brush.left };
+    let filled = fill::flood_fill_tolerance(map, locked, cell, value, tolerance);
+    format!("filled {} cells", filled)
+}
+
+fn handle_right_click(
+    cells: &[(usize, usize)],
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    brush: &Brush,
+    water_level: f64,
+) -> usize {
+    let mut painted = 0;
+    for &(x, y) in cells {
+        painted += paint_brush(map, locked, x, y, brush.right, brush, water_level);
+    }
+    painted
+}
+
+fn handle_left_click(
+    cells: &[(usize, usize)],
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    brush: &Brush,
+    water_level: f64,
+) -> usize {
+    let mut painted = 0;
+    for &(x, y) in cells {
+        painted += paint_brush(map, locked, x, y, brush.left, brush, water_level);
+    }
+    painted
+}
+
+/// The camera after the least scrolling that puts `cell` inside a view of
+/// `view` width and height.
+fn scroll_to_show(
+    cell: (usize, usize),
+    camera: (usize, usize),
+    view: (usize, usize),
+) -> (usize, usize) {
+    let follow = |at: usize, camera: usize, view: usize| {
+        if at < camera {
+            at
+        } else if at >= camera + view {
+            at + 1 - view
+        } else {
+            camera
+        }
+    };
+    (follow(cell.0, camera.0, view.0), follow(cell.1, camera.1, view.1))
+}
+
+/// With `snap_pages` on, moves the camera row after a page jump on to the
+/// next labelled row in the jump's direction, without scrolling past the map.
+fn snap_row(camera_y: usize, forward: bool, viewport: &Viewport, prefs: &Prefs) -> usize {
+    if !prefs.snap_pages {
+        return camera_y;
+    }
+    // Labelled rows are the ones whose shown number is a multiple of the tick
+    let offset = viewport.display_y(0) % prefs.y_tick;
+    let (_, max) = viewport.max_camera();
+    snap_to_tick(camera_y, prefs.y_tick, offset, forward).min(max)
+}
+
+/// The nearest `n * tick + offset` at or after `value` when `forward`, at or
+/// before it otherwise.
+fn snap_to_tick(value: usize, tick: usize, offset: usize, forward: bool) -> usize {
+    let phase = (value + tick - offset) % tick;
+    match (phase, forward) {
+        (0, _) => value,
+        (_, true) => value + tick - phase,
+        (_, false) => value.saturating_sub(phase),
+    }
+}
+
+/// One frame of eased movement from `from` to `to`: a third of the way, but
+/// at least one cell.
+fn ease(from: usize, to: usize) -> usize {
+    let step = from.abs_diff(to).div_ceil(3);
+    if from < to {
+        from + step
+    } else {
+        from - step
+    }
+}
+
+fn generate_noise_map(params: &NoiseParams, size: MapSize) -> Vec<Vec<f64>> {
+    let mut map = size.grid(0.0);
+    terrain::regen_region(&mut map, 0, 0, size.width, size.height, params);
+
+    map
+}
+
+/// Starts regenerating the whole map from `seed`, which stays the seed from
+/// then on.
+fn reseed(map: &[Vec<f64>], noise: &mut NoiseParams, seed: u32) -> Task<Regen> {
+    noise.seed = seed;
+    let done = format!("seed {}", seed);
+    spawn_regen(map, MapSize::of(map).region(), *noise, done)
+}
+
+/// Starts regenerating the whole map in the preset's style, keeping the seed
+/// and, unless the preset has its own, the island mask.
+fn apply_preset(map: &[Vec<f64>], preset: &Preset, noise: &mut NoiseParams) -> Task<Regen> {
+    *noise = NoiseParams {
+        seed: noise.seed,
+        island: preset.noise.island.or(noise.island),
+        ..preset.noise
+    };
+    let done = format!("preset {}", preset.name);
+    spawn_regen(map, MapSize::of(map).region(), *noise, done)
+}
+
+/// Noise regenerated on a worker thread, waiting to be copied into the map.
+struct Regen {
+    cells: Vec<Vec<f64>>,
+    /// Corners as in `terrain::regen_region`, clipped to the map.
+    region: (usize, usize, usize, usize),
+    /// Status message for when it lands.
+    done: String,
+}
+
+impl Regen {
+    /// Copies in only the regenerated region, so edits made elsewhere while
+    /// the worker ran are kept, and skips locked cells.
+    fn apply(self, map: &mut [Vec<f64>], locked: &Locks) -> String {
+        let (x0, y0, x1, y1) = self.region;
+        let rows = map[y0..y1].iter_mut().zip(&self.cells[y0..y1]);
+        for ((row, cells), locked) in rows.zip(&locked[y0..y1]) {
+            for x in (x0..x1).filter(|&x| !locked[x]) {
+                row[x] = cells[x];
+            }
+        }
+        self.done
     }
-  }
 }
 
-fn random_field() -> Point {
-    let mut rng = rand::thread_rng();
-    Point {x: rng.gen_range(0..MAP_WIDTH), y: rng.gen_range(0..MAP_HEIGHT)}
+/// Regenerates `region` of a copy of `map` a row at a time, off the main
+/// thread.
+fn spawn_regen(
+    map: &[Vec<f64>],
+    (x0, y0, x1, y1): (usize, usize, usize, usize),
+    params: NoiseParams,
+    done: String,
+) -> Task<Regen> {
+    let size = MapSize::of(map);
+    let (x1, y1) = (x1.min(size.width), y1.min(size.height));
+    let region = (x0.min(x1), y0.min(y1), x1, y1);
+    let mut cells = map.to_vec();
+    Task::spawn("regenerating", move |progress| {
+        let (x0, y0, x1, y1) = region;
+        progress.set_total(y1 - y0);
+        for y in y0..y1 {
+            terrain::regen_region(&mut cells, x0, y, x1, y + 1, &params);
+            progress.step();
+        }
+        Regen {
+            cells,
+            region,
+            done,
+        }
+    })
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    CombinedLogger::init(vec![WriteLogger::new(
-        LevelFilter::Debug,
-        Config::default(),
-        File::create("gamelive.log").unwrap(),
-    )])
-    .unwrap();
+/// Erodes a copy of `map` off the main thread, reporting each pass.
+fn spawn_erode(map: &[Vec<f64>], droplets: usize, iterations: usize, seed: u32) -> Task<Regen> {
+    let mut cells = map.to_vec();
+    let region = MapSize::of(map).region();
+    Task::spawn("eroding", move |progress| {
+        progress.set_total(iterations);
+        terrain::erode(&mut cells, droplets, iterations, seed, || progress.step());
+        Regen {
+            cells,
+            region,
+            done: format!("eroded with {} droplets x {}", droplets, iterations),
+        }
+    })
+}
 
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    crossterm::execute!(stdout, crossterm::terminal::EnterAlternateScreen)?;
-    crossterm::execute!(io::stdout(), EnableMouseCapture).unwrap();
+/// Rivers carved into a copy of `map`. Quick enough to wait for.
+fn carve_rivers(map: &[Vec<f64>], count: usize, water_level: f64) -> Regen {
+    let mut cells = map.to_vec();
+    let traced = terrain::generate_rivers(&mut cells, count, water_level);
+    Regen {
+        cells,
+        region: MapSize::of(map).region(),
+        done: format!("carved {} rivers", traced),
+    }
+}
 
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+/// Headless `--exec`: runs each line of `path` as a `:` command on `map`.
+/// Blank lines and `#` comments are skipped. The first failing line stops
+/// the script, reported with its line number.
+fn run_script(
+    path: &Path,
+    map: &mut Vec<Vec<f64>>,
+    locked: &mut Locks,
+    annotations: &mut Annotations,
+    noise: &mut NoiseParams,
+    prefs: &mut Prefs,
+    presets: &[Preset],
+) -> Result<(), String> {
+    let content = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+    let full_map = MapSize::of(map).region();
+    let mut current_preset = 0;
+    let mut document = Document::new(None, map, locked);
 
-    let mut map = empty_map();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let at_line = |message: String| format!("{}:{}: {}", path.display(), i + 1, message);
 
-    let mut camera_x = 0;
-    let mut camera_y = 0;
-    let mut term_width = 1;
-    let mut term_height = 1;
-    let mut show_ruller = true;
-    let mut show_help = true;
-    let mut game_state = GameState::Draw;
-    let mut last_frame = Instant::now();
+        // Regeneration goes through the same worker as in the editor, waited on
+        let message = match command::parse_command(line).map_err(at_line)? {
+            Command::Quit => break,
+            Command::Preset(name) => {
+                let found = presets.iter().position(|p| p.name == name);
+                current_preset =
+                    found.ok_or_else(|| at_line(format!("unknown preset '{}'", name)))?;
+                apply_preset(map, &presets[current_preset], noise).join().apply(map, locked)
+            }
+            Command::Seed(seed) => reseed(map, noise, seed).join().apply(map, locked),
+            Command::Regen => {
+                let done = "regenerated the map".to_string();
+                spawn_regen(map, full_map, noise.reroll(), done).join().apply(map, locked)
+            }
+            Command::Erode {
+                droplets,
+                iterations,
+            } => spawn_erode(map, droplets, iterations, noise.seed).join().apply(map, locked),
+            Command::Rivers(count) => {
+                let water_level = presets[current_preset].water_level;
+                carve_rivers(map, count, water_level).apply(map, locked)
+            }
+            Command::Theme(_) | Command::Diff(_) => {
+                return Err(at_line("only available in the editor".to_string()));
+            }
+            command => {
+                let water_level = presets[current_preset].water_level;
+                run_command(command, map, locked, annotations, &mut document, prefs, water_level)
+                    .map_err(at_line)?
+            }
+        };
+        log::info!("{}: {}", line, message);
+    }
 
-    'main_loop:loop {
-        let now = Instant::now();
-        let elapsed = now.duration_since(last_frame);
+    Ok(())
+}
 
-        if elapsed >= FRAME_DURATION {
-            match game_state {
-                GameState::Run => {
-                  let point = random_field();
-                  draw_on_map(&mut map, point.x, point.y, 1.);
-                  //for y in 0..MAP_HEIGHT {
-                  //    for x in 0..MAP_WIDTH {
-                  //        const NEIGHBORS: [(isize, isize); 8] = [
-                  //            (-1, -1), (-1, 0), (-1, 1),
-                  //            (0, -1),          (0, 1),
-                  //            (1, -1),  (1, 0),  (1, 1)];
+/// Headless `gen` subcommand: one thumbnail per seed, no terminal setup.
+fn generate_batch(args: &cli::GenArgs) -> io::Result<()> {
+    fs::create_dir_all(&args.out)?;
 
-                  //        let mut neighbords_count = 0;
+    for seed in args.seeds.clone() {
+        let map = generate_noise_map(&NoiseParams::new(seed), MapSize::default());
+        let path = args
+            .out
+            .join(format!("seed_{}.{}", seed, args.format.extension()));
 
-                  //        for (dx, dy) in NEIGHBORS {
-                  //            let x = (x as isize + dx) % 20;
-                  //            let y = (y as isize + dy) % 20;
+        match args.format {
+            cli::ThumbnailFormat::Png => export::export_png(&map, &path)?,
+            cli::ThumbnailFormat::Txt => {
+                let region = MapSize::of(&map).region();
+                export::export_ascii(&map, region, &path, args.transpose, WATER_LEVEL)?
+            }
+        }
+        log::info!("wrote {}", path.display());
+        println!("{}", path.display());
+    }
 
-                  //            if 0 <= x && x < (MAP_WIDTH - 1) as isize 
-                  //                && 0 <= y && y < (MAP_HEIGHT - 1) as isize {
-                  //                neighbords_count += 1;
-                  //            }
-                  //        }
+    Ok(())
+}
 
-                  //        log::info!("neighbords_count: {}", neighbords_count);
+fn start_map(start: StartMap, noise: &NoiseParams, size: MapSize) -> Vec<Vec<f64>> {
+    match start {
+        StartMap::Empty => empty_map(size),
+        StartMap::Noise => generate_noise_map(noise, size),
+        StartMap::Island => terrain::island_map(noise, size),
+        StartMap::Caves => terrain::caves_map(noise.seed, size),
+    }
+}
 
-                  //        if neighbords_count < 2 || 3 < neighbords_count {
-                  //            draw_on_map(&mut map, x, y, 0.);
-                  //        }
-                  //        if neighbords_count == 3 {
-                  //            draw_on_map(&mut map, x, y, 1.);
-                  //        }
-                  //    }
-                  //}
-                }
-                _ => { }
-            };
-            
-            terminal.draw(|f| {
-                let area = f.area();
-                term_width = area.width as usize;
-                term_height = area.height as usize;
-
-                let map_str = render_map(
-                    &map,
-                    camera_x,
-                    camera_y,
-                    term_width,
-                    term_height,
-                    show_ruller,
-                );
-
-                let paragraph = Paragraph::new(map_str).block(Block::default());
-
-                f.render_widget(paragraph, area);
-
-                if show_help {
-                    let help_area = centered_rect(60, 60, area);
-                    f.render_widget(Clear, help_area); 
-                    let help_paragraph = create_help_paragraph();
-                    f.render_widget(help_paragraph, help_area);
-                }
-            })?;
+fn no_locks(size: MapSize) -> Locks {
+    size.grid(false)
+}
+
+fn empty_map(size: MapSize) -> Vec<Vec<f64>> {
+    let mut map = size.grid(0.);
+    // Markers that don't fit on a small map are left out
+    let mut mark = |x: Option<usize>, y: Option<usize>| {
+        if let Some(cell) = x.zip(y).and_then(|(x, y)| map.get_mut(y)?.get_mut(x)) {
+            *cell = 1.;
         }
+    };
 
-        while event::poll(Duration::from_millis(0))? {
-             let half_height = term_height / 2;
-
-             let (width, height) = if show_ruller {
-                 (
-                     MAP_WIDTH.saturating_sub(term_width - RULLER_LEFT_SIZE),
-                     MAP_HEIGHT.saturating_sub(term_height - RULLER_UP_SIZE),
-                 )
-             } else {
-                 (
-                     MAP_WIDTH.saturating_sub(term_width),
-                     MAP_HEIGHT.saturating_sub(term_height),
-                 )
-             };
-
-             if camera_y > height {
-                 camera_y = height
-             }
-             if camera_x > width {
-                 camera_x = width
-             }
+    mark(Some(0), Some(0));
+    mark(size.width.checked_sub(1), size.height.checked_sub(1));
+    mark(size.width.checked_sub(5), size.height.checked_sub(2));
+    mark(size.width.checked_sub(10), size.height.checked_sub(10));
 
-             if event::poll(std::time::Duration::from_millis(100))? {
-                 match event::read()? {
-                     Event::Key(key) => match key.code {
-                         KeyCode::Char('q') => break 'main_loop,
-                         KeyCode::Char('d') => {
-                             if key.modifiers.contains(KeyModifiers::CONTROL) {
-                                 if camera_y < height - half_height {
-                                     camera_y += half_height;
-                                 } else if camera_y < height {
-                                     camera_y = height;
-                                 }
-                             }
-                         }
-                         KeyCode::Char('u') => {
-                             if key.modifiers.contains(KeyModifiers::CONTROL) {
-                                 if camera_y > half_height {
-                                     camera_y -= half_height;
-                                 } else if camera_y > 0 {
-                                     camera_y = 0;
-                                 }
-                             }
-                         }
-                         KeyCode::Char('r') => {
-                             if key.modifiers.contains(KeyModifiers::CONTROL) {
-                                 show_ruller = !show_ruller;
-                             }
-                         }
-                         KeyCode::Char('h') | KeyCode::Left => {
-                             if camera_x > 0 {
-                                 camera_x -= 1;
-                             }
-                         }
-                         KeyCode::Char('l') | KeyCode::Right => {
-                             if camera_x < width {
-                                 camera_x += 1;
-                             }
-                         }
-                         KeyCode::Char('k') | KeyCode::Up => {
-                             if camera_y > 0 {
-                                 camera_y -= 1;
-                             }
-                         }
-                         KeyCode::Char('j') | KeyCode::Down => {
-                             if camera_y < height {
-                                 camera_y += 1;
-                             }
-                         }
-                         KeyCode::Char('?') => {
-                             show_help = !show_help;
-                         }
-                         KeyCode::Char(' ') => {
-                             game_state.togle_pause();
-                         }
-                         _ => {}
-                     },
-                     Event::Mouse(mouse_event) => {
-                         match game_state {
-                             GameState::Draw =>
-                             match mouse_event.kind {
-                                 MouseEventKind::Down(button) => match button {
-                                     MouseButton::Left => {
-                                         handle_left_click(
-                                             mouse_event.column,
-                                             mouse_event.row,
-                                             &mut map,
-                                             camera_x,
-                                             camera_y,
-                                             show_ruller,
-                                         );
-                                     }
-                                     MouseButton::Right => {
-                                         handle_right_click(
-                                             mouse_event.column,
-                                             mouse_event.row,
-                                             &mut map,
-                                             camera_x,
-                                             camera_y,
-                                             show_ruller,
-                                         );
-                                     }
-                                     _ => {}
-                                 },
-                                 _ => {}
-                             }
-                             _ => {}
-                         }
-                     },
-                     _ => {}
-                 }
-             }
-        }
+    map
+}
 
-        
-        let frame_elapsed = Instant::now().duration_since(now);
-        if frame_elapsed < FRAME_DURATION {
-            std::thread::sleep(FRAME_DURATION - frame_elapsed);
+/// Mean position of all land cells, `None` when everything is under water.
+fn land_centroid(map: &[Vec<f64>], water_level: f64) -> Option<(usize, usize)> {
+    let mut sum_x = 0.;
+    let mut sum_y = 0.;
+    let mut count = 0usize;
+
+    for (y, row) in map.iter().enumerate() {
+        for (x, &value) in row.iter().enumerate() {
+            if value > water_level {
+                sum_x += x as f64;
+                sum_y += y as f64;
+                count += 1;
+            }
         }
     }
 
-    disable_raw_mode()?;
-    crossterm::execute!(io::stdout(), crossterm::terminal::LeaveAlternateScreen)?;
+    if count == 0 {
+        return None;
+    }
 
-    Ok(())
+    let count = count as f64;
+    Some(((sum_x / count).round() as usize, (sum_y / count).round() as usize))
 }
 
-fn calc_adj_mouse(mouse_x: u16, mouse_y: u16, show_ruller: bool) -> (isize, isize) {
-    if show_ruller {
-        (
-            mouse_x as isize - RULLER_LEFT_SIZE as isize,
-            mouse_y as isize - RULLER_UP_SIZE as isize,
-        )
-    } else {
-        (mouse_x as isize, mouse_y as isize)
+/// Glyph for a cell. Values inside ±`VALUE_LIMIT` are water or land by
+/// `water_level`; NaN and anything outside the limit get their own glyphs so
+/// bad data doesn't pass for terrain.
+fn get_char_for_value(value: f64, water_level: f64) -> char {
+    match value {
+        v if v.is_nan() => MISSING,
+        v if v.abs() > VALUE_LIMIT => OUT_OF_RANGE,
+        v if v <= water_level => EMPTY,
+        _ => FILLED,
     }
 }
 
-fn draw_on_map(
-    map: &mut Vec<Vec<f64>>,
-    map_x: usize,
-    map_y: usize,
-    value: f64,
-) {
-   if map_x < MAP_WIDTH && map_y < MAP_HEIGHT {
-       map[map_y][map_x] = value;
-   }
+/// Which glyphs terrain is drawn with.
+#[derive(Clone, Copy)]
+enum Glyphs<'a> {
+    /// Only `FILLED` and `EMPTY`, as text exports use.
+    Plain,
+    Textured,
+    Tiers(&'a Tiers),
 }
 
-fn handle_right_click(
-    mouse_x: u16,
-    mouse_y: u16,
-    map: &mut Vec<Vec<f64>>,
-    camera_x: usize,
-    camera_y: usize,
-    show_ruller: bool,
-) {
-    let (adj_mouse_x, adj_mouse_y) = calc_adj_mouse(mouse_x, mouse_y, show_ruller);
-    if adj_mouse_x >= 0 && adj_mouse_y >= 0 {
-        let map_x = (adj_mouse_x as usize) + camera_x;
-        let map_y = (adj_mouse_y as usize) + camera_y;
-        draw_on_map(map, map_x, map_y, 0.);
+impl<'a> Glyphs<'a> {
+    /// The display toggles' choice; elevation tiers win over texture.
+    fn of(prefs: &'a Prefs) -> Self {
+        if prefs.elevation {
+            Glyphs::Tiers(&prefs.tiers)
+        } else if prefs.textured {
+            Glyphs::Textured
+        } else {
+            Glyphs::Plain
+        }
     }
 }
 
-fn handle_left_click(
-    mouse_x: u16,
-    mouse_y: u16,
-    map: &mut Vec<Vec<f64>>,
-    camera_x: usize,
-    camera_y: usize,
-    show_ruller: bool,
-) {
-    let (adj_mouse_x, adj_mouse_y) = calc_adj_mouse(mouse_x, mouse_y, show_ruller);
-    if adj_mouse_x >= 0 && adj_mouse_y >= 0 {
-        let map_x = (adj_mouse_x as usize) + camera_x;
-        let map_y = (adj_mouse_y as usize) + camera_y;
-        draw_on_map(map, map_x, map_y, 1.);
+/// Glyph for the `zoom`×`zoom` block at `(x, y)`. A block is a wall when
+/// its top-left cell is, otherwise terrain by the mean of its cells.
+fn block_char(
+    map: &[Vec<f64>],
+    x: usize,
+    y: usize,
+    zoom: usize,
+    water_level: f64,
+    glyphs: Glyphs,
+) -> char {
+    if is_wall(map[y][x]) {
+        return wall_char(map, x, y, zoom);
+    }
+    let value = block_value(map, x, y, zoom);
+    match glyphs {
+        Glyphs::Plain => get_char_for_value(value, water_level),
+        Glyphs::Textured => textured_char(value, water_level, x, y),
+        Glyphs::Tiers(tiers) => tier_char(value, water_level, tiers),
     }
 }
 
-fn generate_noise_map() -> Vec<Vec<f64>> {
-    let perlin = Perlin::new(10);
-    let mut map = vec![vec![0.0; MAP_WIDTH]; MAP_HEIGHT];
-
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            let nx = x as f64 / MAP_WIDTH as f64;
-            let ny = y as f64 / MAP_HEIGHT as f64;
-            let noise_value = perlin.get([nx * 10.0, ny * 10.0]);
-            map[y][x] = noise_value;
-        }
+/// `get_char_for_value` split further into the height tiers of `tiers`.
+fn tier_char(value: f64, water_level: f64, tiers: &Tiers) -> char {
+    let height = value - water_level;
+    match get_char_for_value(value, water_level) {
+        EMPTY if height >= -tiers.deep => SHALLOW_WATER,
+        FILLED if height <= tiers.sand => SAND,
+        FILLED if height <= tiers.grass => FILLED,
+        FILLED if height <= tiers.mountain => MOUNTAIN,
+        FILLED => SNOW,
+        glyph => glyph,
     }
-
-    map
 }
 
-fn empty_map() -> Vec<Vec<f64>> {
-    let mut map = vec![vec![0.; MAP_WIDTH]; MAP_HEIGHT];
-
-    map[0][0] = 1.;
-    map[MAP_WIDTH - 1][MAP_HEIGHT - 1] = 1.;
-    map[MAP_WIDTH - 5][MAP_HEIGHT - 2] = 1.;
-    map[MAP_WIDTH - 10][MAP_HEIGHT - 10] = 1.;
+/// Box-drawing glyph joining the wall at `(x, y)` to the walls `step` cells
+/// away on each side.
+fn wall_char(map: &[Vec<f64>], x: usize, y: usize, step: usize) -> char {
+    let wall_at = |x: Option<usize>, y: Option<usize>| match (x, y) {
+        (Some(x), Some(y)) => map.get(y).and_then(|row| row.get(x)).is_some_and(|&v| is_wall(v)),
+        _ => false,
+    };
+    let up = wall_at(Some(x), y.checked_sub(step));
+    let down = wall_at(Some(x), Some(y + step));
+    let left = wall_at(x.checked_sub(step), Some(y));
+    let right = wall_at(Some(x + step), Some(y));
 
-    map
+    match (up, down, left, right) {
+        (false, false, false, false) => '■',
+        (_, _, false, false) => '│',
+        (false, false, _, _) => '─',
+        (false, true, false, true) => '┌',
+        (false, true, true, false) => '┐',
+        (true, false, false, true) => '└',
+        (true, false, true, false) => '┘',
+        (true, true, false, true) => '├',
+        (true, true, true, false) => '┤',
+        (false, true, true, true) => '┬',
+        (true, false, true, true) => '┴',
+        (true, true, true, true) => '┼',
+    }
 }
 
-fn get_char_for_value(value: f64) -> char {
-    match value {
-        v if v <= 0. => EMPTY,
-        _ => FILLED,
+/// `get_char_for_value` with a variant picked by hashing the map cell, so it
+/// never changes between frames or when scrolling.
+fn textured_char(value: f64, water_level: f64, x: usize, y: usize) -> char {
+    // splitmix64 finalizer over the packed coordinates
+    let mut hash = ((x as u64) << 32 | y as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    let hash = (hash ^ (hash >> 31)) as usize;
+    match get_char_for_value(value, water_level) {
+        FILLED => FILLED_VARIANTS[hash % FILLED_VARIANTS.len()],
+        EMPTY => EMPTY_VARIANTS[hash % EMPTY_VARIANTS.len()],
+        glyph => glyph,
     }
 }
 
-fn create_help_paragraph() -> Paragraph<'static> {
-    let help_text = Text::from(vec![
+/// `mouse` is whether the terminal reports mouse events at all.
+fn create_help_paragraph(theme: &Theme, mouse: bool) -> Paragraph<'static> {
+    let mut lines = vec![
         Line::from(Span::styled(
             "Help Menu\n",
             Style::default().add_modifier(Modifier::BOLD),
@@ -379,6 +2976,7 @@ fn create_help_paragraph() -> Paragraph<'static> {
         Line::from("  l, Right Arrow - Move Right"),
         Line::from("  k, Up Arrow    - Move Up"),
         Line::from("  j, Down Arrow  - Move Down"),
+        Line::from("  10j, 3h, ...   - Move a Count of Steps"),
         Line::from(""),
         Line::from(Span::styled(
             "Other Keys:",
@@ -386,24 +2984,109 @@ fn create_help_paragraph() -> Paragraph<'static> {
         )),
         Line::from("  Ctrl+d - Move Down Half Page"),
         Line::from("  Ctrl+u - Move Up Half Page"),
-        Line::from("  Ctrl+r - Toggle Ruler"),
+        Line::from("  Ctrl+h - Move Left Half Page"),
+        Line::from("  Ctrl+l - Move Right Half Page"),
+        Line::from("  PgDn   - Move Down One Page"),
+        Line::from("  PgUp   - Move Up One Page"),
+        Line::from("  Home   - Move to Left Edge"),
+        Line::from("  End    - Move to Right Edge"),
+        Line::from("  g g, G - Move to Top / Bottom Row"),
+        Line::from("  Ctrl+Home/End - Move to Map Corner"),
+        Line::from("  Ctrl+g - Go to a Cell, Typed as x, then y"),
+        Line::from("  o      - Center on Land"),
+        Line::from("  <, >   - Zoom In / Out"),
+        Line::from("  f      - Fit Map to Screen"),
+        Line::from("  Ctrl+r - Toggle Rulers"),
+        Line::from("  Alt+r  - Toggle Bottom and Right Rulers"),
+        Line::from("  Alt+x, Alt+X - Toggle Top / Bottom X Ruler"),
+        Line::from("  Alt+y, Alt+Y - Toggle Left / Right Y Ruler"),
+        Line::from("  Alt+b  - Toggle Half-Block Rendering"),
+        Line::from("  Alt+t  - Toggle Textured Terrain"),
+        Line::from("  Alt+h  - Toggle Heatmap of Raw Cell Values"),
+        Line::from("  Alt+e  - Toggle Elevation Tiers: Water, Sand, Grass, Mountain, Snow"),
+        Line::from("  #      - Toggle Grid, Dimming Water on the Ruler Intervals"),
+        Line::from("  +, -   - Raise / Lower the Sea Level"),
+        Line::from("  Ctrl+w - Type the Sea Level, in Hundredths"),
+        Line::from("  F1     - Toggle Performance HUD"),
         Line::from("  ?      - Toggle Help Menu"),
+        Line::from("  z z    - Reset Camera, Zoom and Display Toggles"),
+        Line::from("  c, C   - Clear the Map to Water / Fill It with Land"),
+        Line::from("  w      - Toggle Drawing Walls"),
+        Line::from("  B      - Toggle Smoothing Brush (Left Drag Softens Terrain)"),
+        Line::from("  x      - Swap Left / Right Button Values"),
+        Line::from("  [, ]   - Shrink / Grow the Brush"),
+        Line::from("  Ctrl+b - Type the Brush Size"),
+        Line::from("  M      - Cycle Paint Mask: Water Only, Land Only, Off"),
+        Line::from("  i      - Toggle Value Inspector"),
+        Line::from("  m      - Toggle Minimap, Framing the Visible Area"),
+        Line::from("  %      - Toggle Map Statistics, Counted When Opened"),
+        Line::from("  n, N   - Jump to Next / Previous Pin"),
+        Line::from("  t      - Toggle Keyboard Cursor: Arrows and hjkl Move It, the View Follows"),
+        Line::from("  V      - Keyboard Selection (y copy, f fill, d clear, r regen, o outline)"),
+        Line::from("  K      - In a Selection or Lasso: Lock / Unlock Its Cells"),
+        Line::from("  Enter, Backspace - Draw / Erase at the Keyboard or Selection Cursor"),
+        Line::from("  v      - Toggle Lasso: Drag a Loop, then y copy, f fill, d clear, o outline"),
+        Line::from("  L      - Toggle Line Tool: Click Both Ends, Right Click Cancels"),
+        Line::from("  R      - Toggle Rectangle Tool: Click Two Corners, Ctrl on the Second Fills"),
+        Line::from("  :      - Command Line (:w1-:w9 save, :e1-:e9 load, Up/Down history)"),
+        Line::from("  :w, :e - Save / Load Map with File Browser (:save is :w)"),
+        Line::from("  Ctrl+s, Ctrl+o - Save / Reload the Current File (map.gml if none)"),
+        Line::from("  Ctrl+z, Ctrl+y - Undo / Redo, a Whole Stroke at a Time"),
+        Line::from("  :export - Export PNG, PGM or Text with File Browser"),
+        Line::from("  :import - Import a Text Map (Unknown Characters Read as Water)"),
+        Line::from("  e, E   - Export the Map / the Visible Cells to map.txt / view.txt"),
+        Line::from("  Ctrl+e - Export the Map as a Grayscale Image to map.pgm"),
+        Line::from("  :fill  - Turn Every Unlocked Cell to Land"),
+        Line::from("  :transpose - Toggle Column-Major Map Files"),
+        Line::from("  :origin - Toggle Counting Rows from the Bottom"),
+        Line::from("  :xtick, :ytick <n> - Cells Between X / Y Ruler Labels"),
+        Line::from("  :smooth - Toggle Eased Camera Movement"),
+        Line::from("  :snap  - Toggle Page Jumps Stopping on Ruler Labels"),
+        Line::from("  :edgepan - Toggle Scrolling When a Drag Reaches the Map Edge"),
+        Line::from("  :regen - Reroll the Noise in View"),
+        Line::from("  Ctrl+n, :seed <n> - Regenerate the Map from a Seed (shown bottom right)"),
+        Line::from("  :erode [drops] [passes] - Carve Valleys with Simulated Rain"),
+        Line::from("  :rivers [count] - Carve Rivers from the Peaks to the Water"),
+        Line::from("  :diff <file> - Color Cells Higher / Lower than Another Map (:diff ends)"),
+        Line::from("  p, :preset <name> - Next / Named Terrain Preset"),
+        Line::from("  :theme <name> - Switch Color Theme"),
+        Line::from("  Alt+z  - Suspend to the Shell, fg Resumes"),
         Line::from("  q      - Quit"),
         Line::from(""),
         Line::from(Span::styled(
             "Mouse Controls:",
             Style::default().add_modifier(Modifier::BOLD),
         )),
-        Line::from("  Left Click  - Draw on Map"),
-        Line::from("  Right Click - Erase from Map"),
-    ]);
+    ];
+    if mouse {
+        lines.extend([
+            Line::from("  Left Click  - Draw on Map"),
+            Line::from("  Right Click - Erase from Map"),
+            Line::from("  Wheel       - Scroll Up / Down, Sideways with Shift"),
+            Line::from("  Ctrl+Wheel  - Raise / Lower the Left Click Value"),
+            Line::from("  s           - Cycle Stamp (Left Click Places)"),
+            Line::from("  S           - Save Visible Area as Stamp"),
+            Line::from("  a           - Add/Edit Note Under Mouse"),
+            Line::from("  A           - Remove Note Under Mouse"),
+            Line::from("  P           - Drop/Remove Pin Under Mouse"),
+            Line::from("  K           - Lock/Unlock Cell Under Mouse (Underlined)"),
+            Line::from("  b, Middle Click - Bucket Fill Under Mouse (:tolerance <value>)"),
+        ]);
+    } else {
+        lines.extend([
+            Line::from("  Not available in this terminal. Press t or V, then Enter draws"),
+            Line::from("  and Backspace erases at the cursor."),
+            Line::from("  S           - Save Visible Area as Stamp"),
+        ]);
+    }
+    let help_text = Text::from(lines);
 
     Paragraph::new(help_text)
         .block(
             Block::default()
                 .title("Help")
                 .borders(Borders::ALL)
-                .border_style(Style::default().fg(Color::Yellow)),
+                .border_style(Style::default().fg(theme.help)),
         )
         .alignment(Alignment::Left)
 }
@@ -438,64 +3121,607 @@ fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     popup_layout[1]
 }
 
-fn render_map(
-    map: &Vec<Vec<f64>>,
-    camera_x: usize,
-    camera_y: usize,
-    width: usize,
-    height: usize,
-    show_ruller: bool,
-) -> String {
-    let mut visible_map = String::new();
+/// Average of the `zoom`×`zoom` block starting at `(map_x, map_y)`, ignoring
+/// cells past the map edge and transparent ones.
+fn block_value(map: &[Vec<f64>], map_x: usize, map_y: usize, zoom: usize) -> f64 {
+    if zoom == 1 {
+        return map[map_y][map_x];
+    }
 
-    // Adjust width and height to account for rulers
-    let (map_width, map_height) = if show_ruller {
-        (width - RULLER_LEFT_SIZE, height - RULLER_UP_SIZE - RULLER_DOWN_SIZE)
+    let mut sum = 0.;
+    let mut count = 0;
+    let size = MapSize::of(map);
+    for row in &map[map_y..(map_y + zoom).min(size.height)] {
+        for &value in &row[map_x..(map_x + zoom).min(size.width)] {
+            if !is_transparent(value) {
+                sum += value;
+                count += 1;
+            }
+        }
+    }
+
+    if count == 0 {
+        TRANSPARENT
     } else {
-        (width, height)
+        sum / count as f64
+    }
+}
+
+/// Terrain color of a cell: the preset's land and water, or with `tiers`
+/// also lighter water, then sand, rock and snow going up.
+fn color_for_value(value: f64, preset: &Preset, theme: &Theme, tiers: Option<&Tiers>) -> Color {
+    if is_wall(value) {
+        return theme.wall;
+    }
+    let palette = theme.palette(preset.palette);
+    let glyph = match tiers {
+        Some(tiers) => tier_char(value, preset.water_level, tiers),
+        None => get_char_for_value(value, preset.water_level),
     };
+    match glyph {
+        FILLED => palette.land,
+        EMPTY => palette.water,
+        SHALLOW_WATER => Color::LightBlue,
+        SAND => Color::Yellow,
+        MOUNTAIN => Color::Rgb(139, 90, 43),
+        SNOW => Color::White,
+        // Missing or out of range
+        _ => theme.invalid,
+    }
+}
+
+/// Lowest and highest terrain value on the map, leaving out walls, holes
+/// and out-of-range cells.
+fn value_range(map: &[Vec<f64>]) -> (f64, f64) {
+    let values = map.iter().flatten().copied();
+    let terrain = values.filter(|&value| !is_wall(value) && value.abs() <= VALUE_LIMIT);
+    terrain.fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| {
+        (low.min(value), high.max(value))
+    })
+}
+
+/// Blue through green to red as `value` goes from the low to the high end of
+/// `range`.
+fn heat_color(value: f64, (low, high): (f64, f64), theme: &Theme) -> Color {
+    if is_wall(value) {
+        return theme.wall;
+    }
+    if is_transparent(value) || value.abs() > VALUE_LIMIT {
+        return theme.invalid;
+    }
+    let t = if high > low { (value - low) / (high - low) } else { 0.5 };
+    let ramp = |t: f64| (t.clamp(0., 1.) * 255.).round() as u8;
+    if t < 0.5 {
+        Color::Rgb(0, ramp(t * 2.), ramp(1. - t * 2.))
+    } else {
+        Color::Rgb(ramp(t * 2. - 1.), ramp(2. - t * 2.), 0)
+    }
+}
+
+/// The map with its rulers and overlays, written cell by cell straight into
+/// the frame buffer so no text needs building for it every frame.
+struct MapView<'a> {
+    map: &'a [Vec<f64>],
+    overlay: &'a Overlay<'a>,
+    preset: &'a Preset,
+    theme: &'a Theme,
+    viewport: &'a Viewport,
+    prefs: &'a Prefs,
+}
 
-    // Top ruler (X-axis)
-    if show_ruller {
-        visible_map.push_str(" ".repeat(RULLER_LEFT_SIZE).as_str()); // Space for Y-axis labels
-        for x in 0..map_width {
-            let map_x = x + camera_x;
-            if map_x % 10 == 0 {
-                let label = format!("{:>2}", map_x % 100);
-                visible_map.push_str(&label);
+impl Widget for MapView<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let MapView {
+            map,
+            overlay,
+            preset,
+            theme,
+            viewport,
+            prefs,
+        } = self;
+        let map_width = viewport.area.width as usize;
+        let map_height = viewport.area.height as usize;
+        let zoom = viewport.col_scale;
+        let row_scale = viewport.row_scale;
+        let camera_x = viewport.camera_x;
+        let size = viewport.size;
+        let camera_y = viewport.camera_y;
+        let left_size = if prefs.show_y_ruler { RULLER_LEFT_SIZE } else { 0 };
+        let right_ruler = prefs.show_right_ruler;
+        let ruler_style = Style::default().fg(theme.ruler);
+        let selected_style = Style::default().add_modifier(Modifier::REVERSED);
+        let is_selected = |map_x: usize, map_y: usize| {
+            let selection = overlay.selection;
+            let under_cursor = |(x, y): (usize, usize)| {
+                (map_x..map_x + zoom).contains(&x) && (map_y..map_y + row_scale).contains(&y)
+            };
+            selection.is_some_and(|selection| selection.overlaps(map_x, map_y, zoom, row_scale))
+                || overlay.lasso.is_some_and(|lasso| lasso.overlaps(map_x, map_y, zoom, row_scale))
+                || overlay.cursor.is_some_and(under_cursor)
+        };
+        let locked_style = Style::default().add_modifier(Modifier::UNDERLINED);
+        let is_locked = |map_x: usize, map_y: usize| {
+            let rows = &overlay.locked[map_y..(map_y + row_scale).min(size.height)];
+            rows.iter().any(|row| row[map_x..(map_x + zoom).min(size.width)].contains(&true))
+        };
+        let heat_range = if prefs.heatmap { value_range(map) } else { (0., 0.) };
+        let glyphs = Glyphs::of(prefs);
+        // Grid lines fall on the labelled columns and rows, and only show on water
+        let grid_style = Style::default().add_modifier(Modifier::DIM);
+        let on_grid_column =
+            |map_x: usize| map_x.div_ceil(prefs.x_tick) * prefs.x_tick < map_x + zoom;
+        let is_grid = |value: f64, map_x: usize, grid_row: bool| {
+            prefs.grid
+                && (grid_row || on_grid_column(map_x))
+                && get_char_for_value(value, preset.water_level) == EMPTY
+        };
+        let terrain_color = |value: f64| {
+            if prefs.heatmap {
+                heat_color(value, heat_range, theme)
             } else {
-                visible_map.push_str("  ");
+                color_for_value(value, preset, theme, prefs.elevation.then_some(&prefs.tiers))
+            }
+        };
+        let diff_color = |map_x: usize, map_y: usize| {
+            let diff = overlay.diff?;
+            match diff::block_diff(diff, map_x, map_y, zoom) {
+                std::cmp::Ordering::Greater => Some(theme.diff_added),
+                std::cmp::Ordering::Less => Some(theme.diff_removed),
+                std::cmp::Ordering::Equal => None,
+            }
+        };
+
+        // Marker glyphs by position inside the map area; pins cover notes and
+        // the line anchor covers both
+        let notes = overlay.annotations.keys().map(|&cell| (cell, ANNOTATION));
+        let pins = overlay.pins.iter().enumerate().map(|(i, &cell)| {
+            let glyph = char::from_digit(i as u32 + 1, 10).filter(|_| i < 9).unwrap_or(PIN);
+            (cell, glyph)
+        });
+        let anchor = overlay.anchor.map(|cell| (cell, ANCHOR));
+        let markers: HashMap<(usize, usize), char> = notes
+            .chain(pins)
+            .chain(anchor)
+            .filter_map(|((x, y), glyph)| {
+                let (col, row) = viewport.map_to_screen(x, y)?;
+                let position = ((col - viewport.area.x) as usize, (row - viewport.area.y) as usize);
+                Some((position, glyph))
+            })
+            .collect();
+
+        // Rows are laid out top to bottom from the corner of `area`, rulers
+        // included, and anything past its edges is cut off
+        let width = area.width as usize;
+        let mut line = area.y;
+
+        // Top ruler (X-axis)
+        if prefs.show_x_ruler && line < area.bottom() {
+            buf.set_stringn(area.x, line, x_ruler(viewport, left_size, prefs), width, ruler_style);
+            line += 1;
+        }
+
+        let first_col = area.x + left_size as u16;
+        let mut label = String::with_capacity(RULLER_LEFT_SIZE);
+        for y in 0..map_height {
+            if line >= area.bottom() {
+                break;
+            }
+            let map_y = y * row_scale + camera_y;
+            let tick = y_tick(map_y, viewport, prefs);
+
+            // Left ruler (Y-axis)
+            if prefs.show_y_ruler {
+                label.clear();
+                match tick {
+                    Some(tick) => {
+                        let _ = write!(label, "{:>3} ", tick % label_modulus(prefs.y_tick));
+                    }
+                    None => {
+                        let _ = write!(label, "{:1$}", "", RULLER_LEFT_SIZE);
+                    }
+                }
+                buf.set_stringn(area.x, line, &label, width, ruler_style);
+            }
+
+            for x in 0..map_width {
+                let col = first_col + x as u16;
+                if col >= area.right() {
+                    break;
+                }
+                let map_x = x * zoom + camera_x;
+
+                let (glyph, style) = if prefs.half_block {
+                    // Upper half is the top cell (fg), lower half the one below (bg)
+                    if size.contains(map_x, map_y) {
+                        let value = block_value(map, map_x, map_y, zoom);
+                        let top = diff_color(map_x, map_y).unwrap_or_else(|| terrain_color(value));
+                        let mut style = Style::default().fg(top);
+                        if is_grid(value, map_x, tick.is_some()) {
+                            style = style.patch(grid_style);
+                        }
+                        if map_y + zoom < size.height {
+                            let bottom = block_value(map, map_x, map_y + zoom, zoom);
+                            let bottom = diff_color(map_x, map_y + zoom)
+                                .unwrap_or_else(|| terrain_color(bottom));
+                            style = style.bg(bottom);
+                        }
+                        if is_locked(map_x, map_y) {
+                            style = style.patch(locked_style);
+                        }
+                        if is_selected(map_x, map_y) {
+                            style = style.patch(selected_style);
+                        }
+                        if let Some(&glyph) = markers.get(&(x, y)) {
+                            (glyph, style.fg(theme.marker))
+                        } else {
+                            (HALF_BLOCK_UPPER, style)
+                        }
+                    } else {
+                        (' ', Style::default())
+                    }
+                } else {
+                    let on_map = size.contains(map_x, map_y);
+                    let glyph = if let Some(&glyph) = markers.get(&(x, y)) {
+                        glyph
+                    } else if on_map && prefs.heatmap {
+                        ' '
+                    } else if on_map {
+                        block_char(map, map_x, map_y, zoom, preset.water_level, glyphs)
+                    } else {
+                        ' '
+                    };
+
+                    let mut style = Style::default();
+                    if on_map {
+                        let value = if is_wall(map[map_y][map_x]) {
+                            WALL
+                        } else {
+                            block_value(map, map_x, map_y, zoom)
+                        };
+                        let color =
+                            diff_color(map_x, map_y).unwrap_or_else(|| terrain_color(value));
+                        style = if prefs.heatmap { style.bg(color) } else { style.fg(color) };
+                        if is_grid(value, map_x, tick.is_some()) {
+                            style = style.patch(grid_style);
+                        }
+                    }
+                    if markers.contains_key(&(x, y)) {
+                        style = style.fg(theme.marker);
+                    }
+                    if on_map && is_locked(map_x, map_y) {
+                        style = style.patch(locked_style);
+                    }
+                    if is_selected(map_x, map_y) {
+                        style = style.patch(selected_style);
+                    }
+                    (glyph, style)
+                };
+                buf[(col, line)].set_char(glyph).set_style(style);
             }
+
+            let right_col = first_col + map_width as u16;
+            if let (Some(tick), true, true) = (tick, right_ruler, right_col < area.right()) {
+                label.clear();
+                let _ = write!(label, " {:<3}", tick % label_modulus(prefs.y_tick));
+                let room = (area.right() - right_col) as usize;
+                buf.set_stringn(right_col, line, &label, room, ruler_style);
+            }
+            line += 1;
         }
-        visible_map.push('\n');
-    }
 
-    for y in 0..map_height {
-        let map_y = y + camera_y;
+        // Bottom ruler, in the row reserved below the map
+        if prefs.show_bottom_ruler && line < area.bottom() {
+            buf.set_stringn(area.x, line, x_ruler(viewport, left_size, prefs), width, ruler_style);
+        }
+    }
+}
 
-        // Left ruler (Y-axis)
-        if show_ruller {
-            if map_y % 5 == 0 {
-                let label = format!("{:>3} ", map_y % 100);
-                visible_map.push_str(&label);
-            } else {
-                visible_map.push_str(" ".repeat(RULLER_LEFT_SIZE).as_str());
+/// Column labels for the visible part of the map, after `indent` spaces left
+/// for the Y ruler.
+fn x_ruler(viewport: &Viewport, indent: usize, prefs: &Prefs) -> String {
+    let map_width = viewport.area.width as usize;
+    let zoom = viewport.col_scale;
+    let mut ruler = String::with_capacity(indent + map_width);
+    let mut label = String::with_capacity(3);
+    ruler.extend(std::iter::repeat(' ').take(indent));
+    // Labels start at their tick column; a tick hidden under the previous
+    // label is skipped
+    let mut x = 0;
+    while x < map_width {
+        let map_x = x * zoom + viewport.camera_x;
+        let tick = map_x.div_ceil(prefs.x_tick) * prefs.x_tick;
+        if tick < map_x + zoom {
+            label.clear();
+            let _ = write!(label, "{}", tick % label_modulus(prefs.x_tick));
+            let label = &label[..label.len().min(map_width - x)];
+            ruler.push_str(label);
+            x += label.len();
+            // Keep neighbouring labels apart
+            if x < map_width {
+                ruler.push(' ');
+                x += 1;
             }
+        } else {
+            ruler.push(' ');
+            x += 1;
         }
+    }
+    ruler
+}
+
+/// Ruler labels keep only their last two digits to stay narrow, or three
+/// once the interval is long enough that two would read 0 at every label.
+fn label_modulus(tick: usize) -> usize {
+    if tick < 100 {
+        100
+    } else {
+        1000
+    }
+}
+
+/// Row label for the screen row starting at map row `map_y`, if one of the
+/// `row_scale` rows it covers falls on a tick.
+fn y_tick(map_y: usize, viewport: &Viewport, prefs: &Prefs) -> Option<usize> {
+    // Shown rows covered by this screen row, lowest first
+    let last_y = (map_y + viewport.row_scale - 1).min(viewport.size.height - 1);
+    let (low, high) = if prefs.origin_bottom {
+        (viewport.display_y(last_y), viewport.display_y(map_y))
+    } else {
+        (map_y, last_y)
+    };
+    let tick = low.div_ceil(prefs.y_tick) * prefs.y_tick;
+    (tick <= high).then_some(tick)
+}
 
-        for x in 0..map_width {
-            let map_x = x + camera_x;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            if map_y < MAP_HEIGHT && map_x < MAP_WIDTH {
-                let value = map[map_y][map_x];
-                let ch = get_char_for_value(value);
-                visible_map.push(ch);
-            } else {
-                visible_map.push(' ');
+    #[test]
+    fn missing_and_out_of_range_values_have_their_own_glyphs() {
+        assert_eq!(get_char_for_value(f64::NAN, WATER_LEVEL), MISSING);
+        assert_eq!(get_char_for_value(TRANSPARENT, WATER_LEVEL), MISSING);
+        assert_eq!(get_char_for_value(f64::NEG_INFINITY, WATER_LEVEL), OUT_OF_RANGE);
+        assert_eq!(get_char_for_value(f64::INFINITY, WATER_LEVEL), OUT_OF_RANGE);
+        assert_eq!(get_char_for_value(1e300, WATER_LEVEL), OUT_OF_RANGE);
+        assert_eq!(get_char_for_value(-VALUE_LIMIT - 0.01, WATER_LEVEL), OUT_OF_RANGE);
+    }
+
+    #[test]
+    fn values_in_range_are_water_or_land() {
+        assert_eq!(get_char_for_value(VALUE_LIMIT, WATER_LEVEL), FILLED);
+        assert_eq!(get_char_for_value(-VALUE_LIMIT, WATER_LEVEL), EMPTY);
+        assert_eq!(get_char_for_value(WATER_LEVEL, WATER_LEVEL), EMPTY);
+        assert_eq!(get_char_for_value(0.3, 0.5), EMPTY);
+        assert_eq!(get_char_for_value(0.6, 0.5), FILLED);
+    }
+
+    #[test]
+    fn script_runs_the_commands_it_lists() {
+        let dir = std::env::temp_dir().join(format!("gamelife-{}-script", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (script, saved) = (dir.join("build.cmds"), dir.join("out.json"));
+        // The request's example, with a `:` typed as on the command line
+        let commands = format!("seed 7\nregen\n:fill\nsave {}\n", saved.display());
+        fs::write(&script, commands).unwrap();
+
+        let size = MapSize {
+            width: 40,
+            height: 30,
+        };
+        let (mut map, mut locked) = (empty_map(size), no_locks(size));
+        let mut annotations = Annotations::new();
+        let mut noise = NoiseParams::new(1);
+        let mut prefs = Prefs::default();
+        let presets = terrain::builtin_presets();
+        let result = run_script(
+            &script,
+            &mut map,
+            &mut locked,
+            &mut annotations,
+            &mut noise,
+            &mut prefs,
+            &presets,
+        );
+        assert_eq!(result, Ok(()));
+        assert_eq!(noise.seed, 7);
+        let (loaded, _, _) = save::load_map(&saved, false, Some(size)).unwrap();
+        assert!(loaded.iter().flatten().all(|&value| value == 1.));
+
+        fs::write(&script, "seed 7\nflood\n").unwrap();
+        let result = run_script(
+            &script,
+            &mut map,
+            &mut locked,
+            &mut annotations,
+            &mut noise,
+            &mut prefs,
+            &presets,
+        );
+        let error = format!("{}:2: unknown command 'flood'", script.display());
+        assert_eq!(result, Err(error));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fill_and_clear_leave_locked_cells_alone() {
+        let mut map = vec![vec![0., 1., TRANSPARENT], vec![-0.5, 0.5, 1.]];
+        let mut locked = no_locks(MapSize::of(&map));
+        locked[1][2] = true;
+
+        assert_eq!(fill_map(&mut map, &locked, 1.), 4);
+        assert_eq!(map, [[1.; 3], [1.; 3]]);
+        assert_eq!(fill_map(&mut map, &locked, 1.), 0);
+
+        map[1][2] = 0.25;
+        assert_eq!(clear_map(&mut map, &locked), 5);
+        assert_eq!(map, [[WATER_LEVEL; 3], [WATER_LEVEL, WATER_LEVEL, 0.25]]);
+
+        // Holes count bit for bit, so filling them with holes changes nothing
+        assert_eq!(fill_map(&mut map, &locked, TRANSPARENT), 5);
+        assert_eq!(fill_map(&mut map, &locked, TRANSPARENT), 0);
+    }
+
+    /// Cells `empty_map` marked, row by row.
+    fn marks(map: &[Vec<f64>]) -> Vec<(usize, usize)> {
+        let cells = map.iter().enumerate().flat_map(|(y, row)| {
+            row.iter().enumerate().filter(|&(_, &value)| value == 1.).map(move |(x, _)| (x, y))
+        });
+        cells.collect()
+    }
+
+    #[test]
+    fn empty_map_marks_non_square_maps() {
+        let map = empty_map(MapSize {
+            width: 30,
+            height: 12,
+        });
+        assert_eq!(map.len(), 12);
+        assert!(map.iter().all(|row| row.len() == 30));
+        assert_eq!(marks(&map), [(0, 0), (20, 2), (25, 10), (29, 11)]);
+
+        let map = empty_map(MapSize {
+            width: 12,
+            height: 30,
+        });
+        assert_eq!(marks(&map), [(0, 0), (2, 20), (7, 28), (11, 29)]);
+    }
+
+    #[test]
+    fn empty_map_leaves_out_markers_that_do_not_fit() {
+        let map = empty_map(MapSize {
+            width: 3,
+            height: 2,
+        });
+        assert_eq!(map.len(), 2);
+        assert!(map.iter().all(|row| row.len() == 3));
+        assert_eq!(marks(&map), [(0, 0), (2, 1)]);
+
+        let map = empty_map(MapSize {
+            width: 1,
+            height: 1,
+        });
+        assert_eq!(marks(&map), [(0, 0)]);
+    }
+
+    /// Draws `map` into `buf` as the main loop does, the buffer standing in
+    /// for the whole terminal.
+    fn render_view(map: &[Vec<f64>], prefs: &Prefs, buf: &mut Buffer) {
+        let (area, _) = split_status_bar(buf.area);
+        let size = MapSize::of(map);
+        let viewport = Viewport::new(area, 0, 0, size, prefs);
+        let locked = no_locks(size);
+        let annotations = Annotations::new();
+        let overlay = Overlay {
+            annotations: &annotations,
+            pins: &[],
+            selection: None,
+            lasso: None,
+            anchor: None,
+            cursor: None,
+            diff: None,
+            locked: &locked,
+        };
+        let map_view = MapView {
+            map,
+            overlay: &overlay,
+            preset: &terrain::builtin_presets()[0],
+            theme: &Theme::default(),
+            viewport: &viewport,
+            prefs,
+        };
+        map_view.render(coords::ruled_area(viewport.area, prefs).intersection(area), buf);
+    }
+
+    /// Rows of a `width`×`height` terminal the map view writes to.
+    fn rendered_rows(size: MapSize, prefs: &Prefs, width: u16, height: u16) -> u16 {
+        const UNTOUCHED: &str = "#";
+        let mut cell = ratatui::buffer::Cell::default();
+        cell.set_symbol(UNTOUCHED);
+        let mut buf = Buffer::filled(Rect::new(0, 0, width, height), cell);
+        render_view(&size.grid(0.), prefs, &mut buf);
+        (0..height)
+            .filter(|&row| (0..width).any(|col| buf[(col, row)].symbol() != UNTOUCHED))
+            .count() as u16
+    }
+
+    #[test]
+    fn map_view_fills_the_usable_height() {
+        for rulers in 0..16 {
+            for half_block in [false, true] {
+                let prefs = Prefs {
+                    show_x_ruler: rulers & 1 != 0,
+                    show_y_ruler: rulers & 2 != 0,
+                    show_bottom_ruler: rulers & 4 != 0,
+                    show_right_ruler: rulers & 8 != 0,
+                    half_block,
+                    ..Prefs::default()
+                };
+                // Taller than the screen, so every row but the status bar is used
+                let rows = rendered_rows(MapSize::default(), &prefs, 80, 24);
+                assert_eq!(rows, 24 - STATUS_BAR_SIZE, "rulers {:04b}", rulers);
+
+                // Shorter than the screen, so only its rows and the rulers are
+                let size = MapSize {
+                    width: 20,
+                    height: 10,
+                };
+                let top = if prefs.show_x_ruler { RULLER_UP_SIZE } else { 0 };
+                let bottom = if prefs.show_bottom_ruler { RULLER_DOWN_SIZE } else { 0 };
+                let map_rows = if half_block { 5 } else { 10 };
+                let rows = rendered_rows(size, &prefs, 80, 24) as usize;
+                assert_eq!(rows, map_rows + top + bottom, "rulers {:04b}", rulers);
             }
         }
-        visible_map.push('\n');
     }
 
-    visible_map
+    /// What `MapView` replaced: a styled span per cell, collected into text
+    /// for a `Paragraph` to lay out every frame.
+    fn render_as_text(map: &[Vec<f64>], prefs: &Prefs, buf: &mut Buffer) {
+        let (area, _) = split_status_bar(buf.area);
+        let viewport = Viewport::new(area, 0, 0, MapSize::of(map), prefs);
+        let (preset, theme) = (&terrain::builtin_presets()[0], Theme::default());
+        let (width, height) = (viewport.area.width as usize, viewport.area.height as usize);
+        let lines: Vec<Line> = map[..height]
+            .iter()
+            .map(|row| {
+                let cells = row[..width].iter().map(|&value| {
+                    let glyph = get_char_for_value(value, preset.water_level);
+                    let color = color_for_value(value, preset, &theme, None);
+                    Span::styled(glyph.to_string(), Style::default().fg(color))
+                });
+                Line::from(cells.collect::<Vec<_>>())
+            })
+            .collect();
+        Paragraph::new(lines).render(viewport.area, buf);
+    }
+
+    /// Mean time `render` takes to draw an 80x50 terminal.
+    fn frame_time(map: &[Vec<f64>], render: impl Fn(&[Vec<f64>], &mut Buffer)) -> Duration {
+        const FRAMES: u32 = 50;
+        let mut buf = Buffer::empty(Rect::new(0, 0, 80, 50));
+        let started = Instant::now();
+        for _ in 0..FRAMES {
+            render(map, &mut buf);
+        }
+        started.elapsed() / FRAMES
+    }
+
+    /// Timings swing with the machine, so this only runs when asked for with
+    /// `cargo test --release -- --ignored`.
+    #[test]
+    #[ignore]
+    fn map_view_draws_faster_than_text() {
+        let size = MapSize::default();
+        let mut map = size.grid(0.);
+        for (y, row) in map.iter_mut().enumerate() {
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = ((x * 7 + y * 3) % 11) as f64 / 5. - 1.;
+            }
+        }
+        let prefs = Prefs::default();
+        let text = frame_time(&map, |map, buf| render_as_text(map, &prefs, buf));
+        let view = frame_time(&map, |map, buf| render_view(map, &prefs, buf));
+        assert!(view < text, "map view took {:?} a frame, text {:?}", view, text);
+    }
 }
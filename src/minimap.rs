@@ -0,0 +1,134 @@
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::Frame;
+
+use crate::coords::Viewport;
+use crate::prefs::Prefs;
+use crate::terrain::Preset;
+use crate::theme::Theme;
+use crate::{color_for_value, get_char_for_value, is_transparent, MapSize, TRANSPARENT};
+
+/// Largest minimap in cells, border excluded.
+const MAX_WIDTH: usize = 40;
+const MAX_HEIGHT: usize = 20;
+
+/// `map` shrunk to `width`×`height` cells, each the mean of the block it
+/// covers. Missing cells are left out of the mean, so a block only stays
+/// missing when nothing else is in it. Neither size may exceed the map's.
+pub fn downsample(map: &[Vec<f64>], width: usize, height: usize) -> Vec<Vec<f64>> {
+    let size = MapSize::of(map);
+    let span = |i: usize, cells: usize, out: usize| (i * cells / out)..((i + 1) * cells / out);
+    (0..height)
+        .map(|row| {
+            let rows = &map[span(row, size.height, height)];
+            (0..width)
+                .map(|col| {
+                    let cols = span(col, size.width, width);
+                    let (mut sum, mut count) = (0., 0);
+                    for &value in rows.iter().flat_map(|row| &row[cols.clone()]) {
+                        if !is_transparent(value) {
+                            sum += value;
+                            count += 1;
+                        }
+                    }
+                    if count == 0 {
+                        TRANSPARENT
+                    } else {
+                        sum / count as f64
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The whole map in the top-right corner of the map area, with a frame
+/// around the part the viewport shows.
+pub fn render(
+    f: &mut Frame,
+    map: &[Vec<f64>],
+    viewport: &Viewport,
+    preset: &Preset,
+    theme: &Theme,
+    prefs: &Prefs,
+) {
+    let size = viewport.size;
+    let area = viewport.area;
+    // One scale for both axes keeps the map's shape
+    let scale = size.width.div_ceil(MAX_WIDTH).max(size.height.div_ceil(MAX_HEIGHT)).max(1);
+    let (width, height) = (size.width.div_ceil(scale), size.height.div_ceil(scale));
+    if width == 0 || height == 0 || area.width < 3 || area.height < 3 {
+        return;
+    }
+    let cells = downsample(map, width, height);
+
+    // Visible cells, as minimap cells from the first to the last inclusive
+    let last_x = (viewport.camera_x + viewport.map_width()).min(size.width) - 1;
+    let last_y = (viewport.camera_y + viewport.map_height()).min(size.height) - 1;
+    let frame = (
+        viewport.camera_x * width / size.width,
+        last_x * width / size.width,
+        viewport.camera_y * height / size.height,
+        last_y * height / size.height,
+    );
+
+    let tiers = prefs.elevation.then_some(&prefs.tiers);
+    let frame_style = Style::default().fg(theme.marker);
+    let lines: Vec<Line> = cells
+        .iter()
+        .enumerate()
+        .map(|(row, values)| {
+            let spans: Vec<Span> = values
+                .iter()
+                .enumerate()
+                .map(|(col, &value)| match frame_char(col, row, frame) {
+                    Some(glyph) => Span::styled(glyph.to_string(), frame_style),
+                    None => {
+                        let glyph = get_char_for_value(value, preset.water_level);
+                        let color = color_for_value(value, preset, theme, tiers);
+                        Span::styled(glyph.to_string(), Style::default().fg(color))
+                    }
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+
+    let popup_width = (width as u16 + 2).min(area.width);
+    let popup_height = (height as u16 + 2).min(area.height);
+    let popup = Rect {
+        x: area.right() - popup_width,
+        y: area.y,
+        width: popup_width,
+        height: popup_height,
+    };
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.popup)),
+    );
+
+    f.render_widget(Clear, popup);
+    f.render_widget(paragraph, popup);
+}
+
+/// Box-drawing glyph for `(col, row)` when it is on the edge of `frame`,
+/// given as first and last column, then first and last row.
+fn frame_char(col: usize, row: usize, frame: (usize, usize, usize, usize)) -> Option<char> {
+    let (left, right, top, bottom) = frame;
+    let on_side = (col == left || col == right) && (top..=bottom).contains(&row);
+    let on_end = (row == top || row == bottom) && (left..=right).contains(&col);
+    match (on_side, on_end) {
+        (true, true) => Some(match (col == left, row == top) {
+            (true, true) => '┌',
+            (false, true) => '┐',
+            (true, false) => '└',
+            (false, false) => '┘',
+        }),
+        (true, false) => Some('│'),
+        (false, true) => Some('─'),
+        (false, false) => None,
+    }
+}
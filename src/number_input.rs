@@ -0,0 +1,137 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::Rect;
+use ratatui::Frame;
+
+use crate::prompt::Prompt;
+use crate::theme::Theme;
+
+/// Integer input limited to `min..=max`, shown like a `Prompt`.
+pub struct NumberInput {
+    label: String,
+    min: isize,
+    max: isize,
+    prompt: Prompt,
+}
+
+pub enum NumberEvent {
+    Pending,
+    Submit(isize),
+    Cancel,
+}
+
+impl NumberInput {
+    pub fn new(label: impl Into<String>, min: isize, max: isize) -> Self {
+        let label = label.into();
+        let title = format!("{} ({} to {})", label, min, max);
+        NumberInput {
+            label,
+            min,
+            max,
+            prompt: Prompt::new(title, ""),
+        }
+    }
+
+    /// The typed value, `None` when it is empty, malformed, overflows or lies
+    /// outside `min..=max`.
+    pub fn value(&self) -> Option<isize> {
+        let value = self.prompt.input.parse::<isize>().ok()?;
+        (self.min..=self.max).contains(&value).then_some(value)
+    }
+
+    /// Only digits and a leading minus sign (when `min` is negative) are
+    /// accepted. Enter on an invalid value keeps the input open.
+    pub fn handle_key(&mut self, key: KeyEvent) -> NumberEvent {
+        let input = &mut self.prompt.input;
+        match key.code {
+            KeyCode::Enter => match self.value() {
+                Some(value) => NumberEvent::Submit(value),
+                None => {
+                    self.prompt.title = format!(
+                        "{}: expected a number from {} to {}",
+                        self.label, self.min, self.max
+                    );
+                    NumberEvent::Pending
+                }
+            },
+            KeyCode::Esc => NumberEvent::Cancel,
+            KeyCode::Backspace => {
+                input.pop();
+                NumberEvent::Pending
+            }
+            KeyCode::Char(ch @ '0'..='9') => {
+                input.push(ch);
+                NumberEvent::Pending
+            }
+            KeyCode::Char('-') if self.min < 0 && input.is_empty() => {
+                input.push('-');
+                NumberEvent::Pending
+            }
+            _ => NumberEvent::Pending,
+        }
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        self.prompt.render(f, area, theme);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn type_keys(input: &mut NumberInput, keys: &str) {
+        for ch in keys.chars() {
+            input.handle_key(KeyEvent::new(KeyCode::Char(ch), KeyModifiers::NONE));
+        }
+    }
+
+    fn enter(input: &mut NumberInput) -> NumberEvent {
+        input.handle_key(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE))
+    }
+
+    #[test]
+    fn empty_input_is_not_submitted() {
+        let mut input = NumberInput::new("Seed", 0, 100);
+        assert_eq!(input.value(), None);
+        assert!(matches!(enter(&mut input), NumberEvent::Pending));
+        assert!(input.prompt.title.contains("expected a number from 0 to 100"));
+    }
+
+    #[test]
+    fn value_in_range_is_submitted() {
+        let mut input = NumberInput::new("Zoom", -5, 5);
+        type_keys(&mut input, "-5");
+        assert_eq!(input.value(), Some(-5));
+        assert!(matches!(enter(&mut input), NumberEvent::Submit(-5)));
+    }
+
+    #[test]
+    fn out_of_range_and_overflow_are_rejected() {
+        let mut input = NumberInput::new("Zoom", 1, 8);
+        type_keys(&mut input, "9");
+        assert_eq!(input.value(), None);
+        assert!(matches!(enter(&mut input), NumberEvent::Pending));
+
+        let mut input = NumberInput::new("Seed", isize::MIN, isize::MAX);
+        type_keys(&mut input, &format!("{}0", isize::MAX));
+        assert_eq!(input.value(), None);
+        input.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE));
+        assert_eq!(input.value(), Some(isize::MAX));
+    }
+
+    #[test]
+    fn minus_only_leads_when_negatives_are_allowed() {
+        let mut input = NumberInput::new("Width", 1, 100);
+        type_keys(&mut input, "-4");
+        assert_eq!(input.prompt.input, "4");
+
+        let mut input = NumberInput::new("Offset", -100, 100);
+        type_keys(&mut input, "4-x2");
+        assert_eq!(input.prompt.input, "42");
+        assert!(matches!(
+            input.handle_key(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE)),
+            NumberEvent::Cancel
+        ));
+    }
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const PREFS_FILE: &str = "prefs.json";
+
+/// UI state restored between runs. Map data is never stored here.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Prefs {
+    /// X ruler above the map.
+    pub show_x_ruler: bool,
+    /// Y ruler left of the map.
+    pub show_y_ruler: bool,
+    /// X ruler again below the map.
+    pub show_bottom_ruler: bool,
+    /// Y ruler again right of the map.
+    pub show_right_ruler: bool,
+    pub show_help: bool,
+    /// Cells between X ruler labels.
+    pub x_tick: usize,
+    /// Cells between Y ruler labels.
+    pub y_tick: usize,
+    /// Map cells per screen character along each axis.
+    pub zoom: usize,
+    /// Render two map rows per terminal row with colored half blocks.
+    pub half_block: bool,
+    /// Raw values around the hovered cell in a corner popup.
+    pub show_inspector: bool,
+    /// The whole map shrunk into a corner, framing what is on screen.
+    pub show_minimap: bool,
+    /// Mix glyph variants into uniform terrain.
+    pub textured: bool,
+    /// Color cells by their raw value instead of drawing terrain.
+    pub heatmap: bool,
+    /// Draw terrain in height tiers rather than just land and water.
+    pub elevation: bool,
+    pub tiers: Tiers,
+    /// Dim water on the rows and columns the rulers label.
+    pub grid: bool,
+    /// Initial map generator: empty, noise, island or caves.
+    pub start: String,
+    /// Write and read map files and text exports column-major.
+    pub transpose_files: bool,
+    /// Count rows up from the bottom edge in everything shown to the user.
+    /// The map itself is still stored top row first.
+    pub origin_bottom: bool,
+    /// Keep `:` command history on disk between runs.
+    pub save_history: bool,
+    /// Ease the camera towards where it was sent over a few frames.
+    pub smooth_pan: bool,
+    /// Page jumps stop on a labelled ruler row.
+    pub snap_pages: bool,
+    /// Dragging against the edge of the map area scrolls it.
+    pub edge_pan: bool,
+    /// How far from the clicked value the bucket fill spreads.
+    pub fill_tolerance: f64,
+    /// Name of the color theme.
+    pub theme: String,
+}
+
+impl Prefs {
+    /// Puts the zoom and every display toggle back to the defaults and
+    /// closes the help. Ticks, file settings and the row origin are kept.
+    pub fn reset_view(&mut self) {
+        let defaults = Prefs::default();
+        *self = Prefs {
+            show_x_ruler: defaults.show_x_ruler,
+            show_y_ruler: defaults.show_y_ruler,
+            show_bottom_ruler: defaults.show_bottom_ruler,
+            show_right_ruler: defaults.show_right_ruler,
+            show_help: false,
+            zoom: defaults.zoom,
+            half_block: defaults.half_block,
+            show_inspector: defaults.show_inspector,
+            show_minimap: defaults.show_minimap,
+            textured: defaults.textured,
+            heatmap: defaults.heatmap,
+            elevation: defaults.elevation,
+            grid: defaults.grid,
+            ..std::mem::take(self)
+        };
+    }
+}
+
+impl Default for Prefs {
+    fn default() -> Self {
+        Prefs {
+            show_x_ruler: true,
+            show_y_ruler: true,
+            show_bottom_ruler: false,
+            show_right_ruler: false,
+            show_help: true,
+            x_tick: 10,
+            y_tick: 5,
+            zoom: 1,
+            half_block: false,
+            show_inspector: false,
+            show_minimap: false,
+            textured: false,
+            heatmap: false,
+            elevation: false,
+            tiers: Tiers::default(),
+            grid: false,
+            start: "empty".to_string(),
+            transpose_files: false,
+            origin_bottom: false,
+            save_history: true,
+            smooth_pan: false,
+            snap_pages: false,
+            edge_pan: false,
+            fill_tolerance: 0.,
+            theme: "default".to_string(),
+        }
+    }
+}
+
+/// Heights, measured from the preset's water level, where the elevation
+/// view changes tier.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tiers {
+    /// Water deeper than this is deep water, the rest shallow.
+    pub deep: f64,
+    /// Land up to this high is sand.
+    pub sand: f64,
+    /// Then grass up to this.
+    pub grass: f64,
+    /// Then mountain up to this, and snow above.
+    pub mountain: f64,
+}
+
+impl Default for Tiers {
+    fn default() -> Self {
+        Tiers {
+            deep: 0.3,
+            sand: 0.08,
+            grass: 0.4,
+            mountain: 0.7,
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/gamelive`, falling back to `~/.config/gamelive` and
+/// finally to the working directory.
+pub fn config_dir() -> PathBuf {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    match config_dir {
+        Some(dir) => dir.join("gamelive"),
+        None => PathBuf::from("."),
+    }
+}
+
+pub fn prefs_path() -> PathBuf {
+    config_dir().join(PREFS_FILE)
+}
+
+/// Missing or unreadable files fall back to the defaults.
+pub fn load_prefs(path: &Path) -> Prefs {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) => {
+            if err.kind() != io::ErrorKind::NotFound {
+                log::warn!("failed to read prefs {}: {}", path.display(), err);
+            }
+            return Prefs::default();
+        }
+    };
+
+    let mut prefs: Prefs = serde_json::from_str(&content).unwrap_or_else(|err| {
+        log::warn!("failed to parse prefs {}: {}", path.display(), err);
+        Prefs::default()
+    });
+    // Zero ticks or zoom would divide by zero while rendering.
+    prefs.x_tick = prefs.x_tick.max(1);
+    prefs.y_tick = prefs.y_tick.max(1);
+    prefs.zoom = prefs.zoom.max(1);
+
+    prefs
+}
+
+pub fn save_prefs(prefs: &Prefs, path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let content = serde_json::to_string_pretty(prefs)?;
+    fs::write(path, content)
+}
@@ -0,0 +1,77 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Tasks finishing sooner than this never show up in the status bar.
+const SHOW_AFTER: Duration = Duration::from_millis(200);
+const SPINNER: [char; 4] = ['◐', '◓', '◑', '◒'];
+
+/// How far a long loop has got, bumped by the worker and read by the main
+/// loop.
+#[derive(Default)]
+pub struct Progress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl Progress {
+    pub fn set_total(&self, total: usize) {
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Marks one more unit of work as done.
+    pub fn step(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn percent(&self) -> usize {
+        match self.total.load(Ordering::Relaxed) {
+            0 => 0,
+            total => (self.done.load(Ordering::Relaxed) * 100 / total).min(100),
+        }
+    }
+}
+
+/// A long operation running on its own thread, so the UI keeps drawing.
+pub struct Task<T> {
+    label: String,
+    progress: Arc<Progress>,
+    started: Instant,
+    handle: JoinHandle<T>,
+}
+
+impl<T: Send + 'static> Task<T> {
+    pub fn spawn(label: &str, work: impl FnOnce(&Progress) -> T + Send + 'static) -> Self {
+        let progress = Arc::new(Progress::default());
+        let shared = Arc::clone(&progress);
+        Task {
+            label: label.to_string(),
+            progress,
+            started: Instant::now(),
+            handle: thread::spawn(move || work(&shared)),
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the result. A panic in the worker is passed on.
+    pub fn join(self) -> T {
+        self.handle
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    /// Spinner, label and percentage for the status bar, once the task has
+    /// run long enough to be worth mentioning.
+    pub fn status(&self) -> Option<String> {
+        let elapsed = self.started.elapsed();
+        if elapsed < SHOW_AFTER {
+            return None;
+        }
+        let frame = SPINNER[(elapsed.as_millis() / 100) as usize % SPINNER.len()];
+        Some(format!("{} {} {}%", frame, self.label, self.progress.percent()))
+    }
+}
@@ -0,0 +1,70 @@
+use crossterm::event::{KeyCode, KeyEvent};
+use ratatui::layout::Rect;
+use ratatui::style::Style;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+use ratatui::Frame;
+
+use crate::theme::Theme;
+
+/// Single-line text input shown in a popup.
+pub struct Prompt {
+    pub title: String,
+    pub input: String,
+}
+
+pub enum PromptEvent {
+    Pending,
+    Submit(String),
+    Cancel,
+}
+
+impl Prompt {
+    pub fn new(title: impl Into<String>, input: impl Into<String>) -> Self {
+        Prompt {
+            title: title.into(),
+            input: input.into(),
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PromptEvent {
+        match key.code {
+            KeyCode::Enter => PromptEvent::Submit(std::mem::take(&mut self.input)),
+            KeyCode::Esc => PromptEvent::Cancel,
+            KeyCode::Backspace => {
+                self.input.pop();
+                PromptEvent::Pending
+            }
+            KeyCode::Char(ch) => {
+                self.input.push(ch);
+                PromptEvent::Pending
+            }
+            _ => PromptEvent::Pending,
+        }
+    }
+
+    pub fn render(&self, f: &mut Frame, area: Rect, theme: &Theme) {
+        let popup = popup_rect(60, 3, area);
+        let paragraph = Paragraph::new(format!("{}_", self.input)).block(
+            Block::default()
+                .title(self.title.as_str())
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(theme.popup)),
+        );
+
+        f.render_widget(Clear, popup);
+        f.render_widget(paragraph, popup);
+    }
+}
+
+/// `percent_x` of the width, `height` rows tall, centered in `area`.
+fn popup_rect(percent_x: u16, height: u16, area: Rect) -> Rect {
+    let width = (area.width as u32 * percent_x as u32 / 100) as u16;
+    let height = height.min(area.height);
+
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
@@ -0,0 +1,314 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{no_locks, Annotations, Locks, MapSize, TRANSPARENT};
+
+/// Map file version written by `save_map`. Version 1 files were the bare
+/// grid, version 2 had no checksum and version 3 no notes.
+pub const MAP_VERSION: u32 = 4;
+
+/// A saved map from version 2 on.
+#[derive(Serialize, Deserialize)]
+struct MapFile {
+    version: u32,
+    /// `checksum` of `cells` as hex, from version 3 on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checksum: Option<String>,
+    /// Rows, or columns for transposed files. JSON has no NaN or infinity,
+    /// so missing and infinite cells are `null` and read back as missing.
+    cells: Vec<Vec<Option<f64>>>,
+    /// `(x, y)` of each locked cell, always in map order. Left out when
+    /// nothing is locked.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    locked: Vec<(usize, usize)>,
+    /// `(x, y)` and text of each note, in map order like `locked`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    annotations: Vec<(usize, usize, String)>,
+}
+
+/// Just enough of a versioned file to tell which version it is.
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// `map<slot>.json` in the working directory.
+pub fn slot_path(slot: u8) -> PathBuf {
+    PathBuf::from(format!("map{}.json", slot))
+}
+
+fn is_ron(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "ron")
+}
+
+/// Serializes as RON for `.ron` paths and as JSON otherwise. RON output puts
+/// each row of a map file's grid on one line so it stays editable by hand.
+pub fn encode<T: Serialize>(value: &T, path: &Path) -> io::Result<String> {
+    if is_ron(path) {
+        let config = ron::ser::PrettyConfig::new().depth_limit(2);
+        ron::ser::to_string_pretty(value, config)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    } else {
+        Ok(serde_json::to_string(value)?)
+    }
+}
+
+/// Counterpart of `encode`, picking the format from the extension.
+pub fn decode<T: DeserializeOwned>(content: &str, path: &Path) -> io::Result<T> {
+    if is_ron(path) {
+        ron::from_str(content).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    } else {
+        Ok(serde_json::from_str(content)?)
+    }
+}
+
+/// Swaps rows and columns, for tools that expect column-major grids.
+pub fn transpose(map: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let width = map.first().map_or(0, Vec::len);
+    (0..width)
+        .map(|x| map.iter().map(|row| row[x]).collect())
+        .collect()
+}
+
+/// Writes the map as JSON (or RON, for `.ron` paths) tagged with
+/// `MAP_VERSION`, its cells as an array of rows, or of columns when
+/// `transpose` is set, followed by the locked cells and the notes.
+pub fn save_map(
+    map: &[Vec<f64>],
+    locked: &Locks,
+    annotations: &Annotations,
+    path: &Path,
+    transpose: bool,
+) -> io::Result<()> {
+    let cells = if transpose {
+        self::transpose(map)
+    } else {
+        map.to_vec()
+    };
+    let cells: Vec<Vec<Option<f64>>> = cells
+        .iter()
+        .map(|row| row.iter().map(|&value| value.is_finite().then_some(value)).collect())
+        .collect();
+    let file = MapFile {
+        version: MAP_VERSION,
+        checksum: Some(format!("{:016x}", checksum(&cells))),
+        cells,
+        locked: locked_cells(locked),
+        annotations: notes(annotations),
+    };
+    fs::write(path, encode(&file, path)?)
+}
+
+/// 64-bit FNV-1a over the bits of every cell as it is read back, in file
+/// order. Cheap to compute and enough to notice a damaged or hand-mangled
+/// file.
+fn checksum(cells: &[Vec<Option<f64>>]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x100_0000_01b3;
+    let values = cells.iter().flatten().map(|cell| cell.unwrap_or(TRANSPARENT));
+    let bytes = values.flat_map(|value| value.to_bits().to_le_bytes());
+    bytes.fold(OFFSET, |hash, byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
+fn locked_cells(locked: &Locks) -> Vec<(usize, usize)> {
+    let mut cells = Vec::new();
+    for (y, row) in locked.iter().enumerate() {
+        for (x, _) in row.iter().enumerate().filter(|(_, &cell)| cell) {
+            cells.push((x, y));
+        }
+    }
+    cells
+}
+
+fn notes(annotations: &Annotations) -> Vec<(usize, usize, String)> {
+    let mut notes: Vec<_> = annotations
+        .iter()
+        .map(|(&(x, y), text)| (x, y, text.clone()))
+        .collect();
+    notes.sort_by_key(|&(x, y, _)| (y, x));
+    notes
+}
+
+/// Brings a map file of any known version up to date. Files from a newer
+/// build are refused rather than half read.
+fn migrate(content: &str, path: &Path) -> io::Result<MapFile> {
+    // Version 1 had no header, only the grid
+    if let Ok(cells) = decode::<Vec<Vec<Option<f64>>>>(content, path) {
+        return Ok(MapFile {
+            version: 1,
+            checksum: None,
+            cells,
+            locked: Vec::new(),
+            annotations: Vec::new(),
+        });
+    }
+
+    let header: Header = decode(content, path)?;
+    match header.version {
+        2 => decode(content, path),
+        3 | MAP_VERSION => {
+            let file: MapFile = decode(content, path)?;
+            let expected = file.checksum.as_deref().unwrap_or_default();
+            let actual = format!("{:016x}", checksum(&file.cells));
+            if expected != actual {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} is damaged: checksum {} does not match its cells ({})",
+                        path.display(),
+                        if expected.is_empty() { "missing" } else { expected },
+                        actual
+                    ),
+                ));
+            }
+            Ok(file)
+        }
+        version if version > MAP_VERSION => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} is map version {}, newer than the {} this build reads",
+                path.display(),
+                version,
+                MAP_VERSION
+            ),
+        )),
+        version => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} has unknown map version {}", path.display(), version),
+        )),
+    }
+}
+
+/// Reads a map written by `save_map` with the same `transpose` setting, or
+/// by an older version of it, along with which cells are locked and the
+/// notes. Maps of any other size than `size` are refused; without one, any
+/// map with rows of the same length is read.
+pub fn load_map(
+    path: &Path,
+    transpose: bool,
+    size: Option<MapSize>,
+) -> io::Result<(Vec<Vec<f64>>, Locks, Annotations)> {
+    let content = fs::read_to_string(path)?;
+    let file = migrate(&content, path)?;
+    let mut map: Vec<Vec<f64>> = file
+        .cells
+        .into_iter()
+        .map(|row| row.into_iter().map(|cell| cell.unwrap_or(TRANSPARENT)).collect())
+        .collect();
+    if transpose {
+        map = self::transpose(&map);
+    }
+
+    let expected = size.unwrap_or_else(|| MapSize::of(&map));
+    let fits = |size: MapSize| {
+        size.width > 0 && map.len() == size.height && map.iter().all(|row| row.len() == size.width)
+    };
+    if !fits(expected) {
+        let message = match size {
+            Some(size) => format!("{} is not a {}x{} map", path.display(), size.width, size.height),
+            None => format!("{} is not a rectangular map", path.display()),
+        };
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    let size = expected;
+
+    let mut locked = no_locks(size);
+    for (x, y) in file.locked {
+        match locked.get_mut(y).and_then(|row| row.get_mut(x)) {
+            Some(cell) => *cell = true,
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} locks ({}, {}), outside the map", path.display(), x, y),
+                ))
+            }
+        }
+    }
+
+    let mut annotations = Annotations::new();
+    for (x, y, text) in file.annotations {
+        if !size.contains(x, y) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} has a note at ({}, {}), outside the map", path.display(), x, y),
+            ));
+        }
+        annotations.insert((x, y), text);
+    }
+
+    Ok((map, locked, annotations))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `name` in the system temp directory, unique to this test run.
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("gamelife-{}-{}", std::process::id(), name))
+    }
+
+    fn same_cells(a: &[Vec<f64>], b: &[Vec<f64>]) -> bool {
+        a.len() == b.len()
+            && a.iter().flatten().zip(b.iter().flatten()).all(|(a, b)| a.to_bits() == b.to_bits())
+    }
+
+    #[test]
+    fn round_trip_keeps_missing_cells_locks_and_notes() {
+        let size = MapSize { width: 3, height: 2 };
+        let map = vec![vec![0.5, TRANSPARENT, -1.], vec![2., 0., TRANSPARENT]];
+        let mut locked = no_locks(size);
+        locked[1][2] = true;
+        let annotations = Annotations::from([
+            ((2, 0), "peak".to_string()),
+            ((0, 1), String::new()),
+        ]);
+
+        let files = [("plain.json", false), ("plain.ron", false), ("cols.json", true)];
+        for (name, transpose) in files {
+            let path = temp_path(name);
+            save_map(&map, &locked, &annotations, &path, transpose).unwrap();
+            let loaded = load_map(&path, transpose, Some(size));
+            fs::remove_file(&path).unwrap();
+
+            let (cells, loaded_locks, loaded_notes) = loaded.unwrap();
+            assert!(same_cells(&cells, &map), "{} changed the cells", name);
+            assert_eq!(loaded_locks, locked, "{} changed the locks", name);
+            assert_eq!(loaded_notes, annotations, "{} changed the notes", name);
+        }
+    }
+
+    #[test]
+    fn infinite_cells_load_as_missing() {
+        let size = MapSize { width: 2, height: 1 };
+        let path = temp_path("infinite.json");
+        let (locked, notes) = (no_locks(size), Annotations::new());
+        save_map(&[vec![f64::INFINITY, 1.]], &locked, &notes, &path, false).unwrap();
+        let loaded = load_map(&path, false, Some(size));
+        fs::remove_file(&path).unwrap();
+
+        let (cells, _, _) = loaded.unwrap();
+        assert!(same_cells(&cells, &[vec![TRANSPARENT, 1.]]));
+    }
+
+    #[test]
+    fn version_3_files_load_without_notes() {
+        let size = MapSize { width: 2, height: 1 };
+        let cells = vec![vec![Some(1.), None]];
+        let content = format!(
+            r#"{{"version":3,"checksum":"{:016x}","cells":[[1.0,null]],"locked":[[1,0]]}}"#,
+            checksum(&cells)
+        );
+        let path = temp_path("version3.json");
+        fs::write(&path, content).unwrap();
+        let loaded = load_map(&path, false, Some(size));
+        fs::remove_file(&path).unwrap();
+
+        let (_, locked, notes) = loaded.unwrap();
+        assert!(locked[0][1]);
+        assert!(notes.is_empty());
+    }
+}
@@ -0,0 +1,236 @@
+use crate::stamp;
+use crate::{Locks, MapSize, TRANSPARENT};
+
+/// Rectangle of map cells between a fixed anchor and a moving cursor, both
+/// corners included.
+#[derive(Clone, Copy)]
+pub struct Selection {
+    pub anchor: (usize, usize),
+    pub cursor: (usize, usize),
+    /// Map the cursor is kept inside of.
+    size: MapSize,
+}
+
+impl Selection {
+    /// A single cell selection at `(x, y)`, clamped to the map.
+    pub fn new(x: usize, y: usize, size: MapSize) -> Self {
+        let corner = (x.min(size.width - 1), y.min(size.height - 1));
+        Selection {
+            anchor: corner,
+            cursor: corner,
+            size,
+        }
+    }
+
+    /// Moves the cursor corner, stopping at the map edges. Returns whether it
+    /// moved at all.
+    pub fn move_cursor(&mut self, dx: isize, dy: isize) -> bool {
+        let (x, y) = self.cursor;
+        self.cursor = (
+            x.saturating_add_signed(dx).min(self.size.width - 1),
+            y.saturating_add_signed(dy).min(self.size.height - 1),
+        );
+        self.cursor != (x, y)
+    }
+
+    /// Top-left corner, width and height.
+    pub fn rect(&self) -> (usize, usize, usize, usize) {
+        let (ax, ay) = self.anchor;
+        let (cx, cy) = self.cursor;
+        let (x, y) = (ax.min(cx), ay.min(cy));
+        (x, y, ax.max(cx) - x + 1, ay.max(cy) - y + 1)
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        self.overlaps(x, y, 1, 1)
+    }
+
+    /// Whether any cell of the `width`×`height` block at `(x, y)` is selected.
+    pub fn overlaps(&self, x: usize, y: usize, width: usize, height: usize) -> bool {
+        let (sel_x, sel_y, sel_width, sel_height) = self.rect();
+        x < sel_x + sel_width && sel_x < x + width && y < sel_y + sel_height && sel_y < y + height
+    }
+
+    /// Sets every selected cell that isn't locked to `value`.
+    pub fn fill(&self, map: &mut [Vec<f64>], locked: &Locks, value: f64) {
+        let (x, y, width, height) = self.rect();
+        for (row, locked) in map[y..y + height].iter_mut().zip(&locked[y..y + height]) {
+            for (cell, _) in row[x..x + width]
+                .iter_mut()
+                .zip(&locked[x..x + width])
+                .filter(|(_, &locked)| !locked)
+            {
+                *cell = value;
+            }
+        }
+    }
+
+    /// Locks every selected cell, or unlocks them all if they already are.
+    /// Returns whether they ended up locked.
+    pub fn toggle_lock(&self, locked: &mut Locks) -> bool {
+        let (x, y, width, height) = self.rect();
+        let rows = &mut locked[y..y + height];
+        let lock = !rows.iter().all(|row| row[x..x + width].iter().all(|&cell| cell));
+        for row in rows {
+            row[x..x + width].fill(lock);
+        }
+        lock
+    }
+}
+
+/// Freeform loop dragged with the mouse. While it is being drawn only the
+/// traced cells count; once closed, every cell inside the loop does.
+pub struct Lasso {
+    points: Vec<(usize, usize)>,
+    /// A flag per map cell, empty until the loop is closed.
+    cells: Vec<Vec<bool>>,
+}
+
+impl Lasso {
+    pub fn new(x: usize, y: usize) -> Self {
+        Lasso {
+            points: vec![(x, y)],
+            cells: Vec::new(),
+        }
+    }
+
+    /// Adds the next traced cell, skipping repeats.
+    pub fn push(&mut self, x: usize, y: usize) {
+        if self.points.last() != Some(&(x, y)) {
+            self.points.push((x, y));
+        }
+    }
+
+    /// Joins the last point back to the first and selects what the loop
+    /// encloses on a map of `size`.
+    pub fn close(&mut self, size: MapSize) {
+        self.cells = select_polygon(&self.points, size);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        !self.cells.is_empty()
+    }
+
+    /// Smallest rectangle holding the whole loop: top-left corner, width and
+    /// height.
+    pub fn bounds(&self) -> (usize, usize, usize, usize) {
+        let min_x = self.points.iter().map(|p| p.0).min().unwrap_or(0);
+        let max_x = self.points.iter().map(|p| p.0).max().unwrap_or(0);
+        let min_y = self.points.iter().map(|p| p.1).min().unwrap_or(0);
+        let max_y = self.points.iter().map(|p| p.1).max().unwrap_or(0);
+        (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+    }
+
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        if self.is_closed() {
+            self.cells
+                .get(y)
+                .and_then(|row| row.get(x))
+                .copied()
+                .unwrap_or(false)
+        } else {
+            self.points.contains(&(x, y))
+        }
+    }
+
+    /// Whether any cell of the `width`×`height` block at `(x, y)` is selected.
+    pub fn overlaps(&self, x: usize, y: usize, width: usize, height: usize) -> bool {
+        let (min_x, min_y, bounds_width, bounds_height) = self.bounds();
+        let x_range = x.max(min_x)..(x + width).min(min_x + bounds_width);
+        let y_range = y.max(min_y)..(y + height).min(min_y + bounds_height);
+        y_range
+            .into_iter()
+            .any(|y| x_range.clone().any(|x| self.contains(x, y)))
+    }
+
+    /// Sets every selected cell that isn't locked to `value`.
+    pub fn fill(&self, map: &mut [Vec<f64>], locked: &Locks, value: f64) {
+        for ((row, selected), locked) in map.iter_mut().zip(&self.cells).zip(locked) {
+            for (cell, _) in row
+                .iter_mut()
+                .zip(selected.iter().zip(locked))
+                .filter(|(_, (&selected, &locked))| selected && !locked)
+            {
+                *cell = value;
+            }
+        }
+    }
+
+    /// Locks every selected cell, or unlocks them all if they already are.
+    /// Returns whether they ended up locked.
+    pub fn toggle_lock(&self, locked: &mut Locks) -> bool {
+        let selected = || {
+            locked
+                .iter()
+                .flatten()
+                .zip(self.cells.iter().flatten())
+                .filter(|(_, &selected)| selected)
+        };
+        let lock = !selected().all(|(&locked, _)| locked);
+        for (row, selected) in locked.iter_mut().zip(&self.cells) {
+            for (cell, _) in row.iter_mut().zip(selected).filter(|(_, &selected)| selected) {
+                *cell = lock;
+            }
+        }
+        lock
+    }
+
+    /// The selected cells within `bounds`, everything else transparent.
+    pub fn copy(&self, map: &[Vec<f64>]) -> Vec<Vec<f64>> {
+        let (x, y, width, height) = self.bounds();
+        let mut region = stamp::copy_region(map, x, y, width, height);
+        for (dy, row) in region.iter_mut().enumerate() {
+            for (dx, cell) in row.iter_mut().enumerate() {
+                if !self.contains(x + dx, y + dy) {
+                    *cell = TRANSPARENT;
+                }
+            }
+        }
+        region
+    }
+}
+
+/// Cells inside the closed polygon through `points`, plus the cells its edges
+/// pass over. Inside means the cell centre passes an even-odd crossing test.
+pub fn select_polygon(points: &[(usize, usize)], size: MapSize) -> Vec<Vec<bool>> {
+    let mut cells = size.grid(false);
+    let edges: Vec<((f64, f64), (f64, f64))> = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(&(ax, ay), &(bx, by))| ((ax as f64, ay as f64), (bx as f64, by as f64)))
+        .collect();
+
+    for (y, row) in cells.iter_mut().enumerate() {
+        let y = y as f64;
+        // Where the edges cross this row's centre line, left to right
+        let mut crossings: Vec<f64> = edges
+            .iter()
+            .filter(|((_, ay), (_, by))| (*ay > y) != (*by > y))
+            .map(|((ax, ay), (bx, by))| ax + (y - ay) * (bx - ax) / (by - ay))
+            .collect();
+        crossings.sort_by(f64::total_cmp);
+
+        for pair in crossings.chunks_exact(2) {
+            let start = pair[0].ceil().max(0.) as usize;
+            let end = (pair[1].floor().max(-1.) + 1.) as usize;
+            for cell in row.iter_mut().take(end).skip(start) {
+                *cell = true;
+            }
+        }
+    }
+
+    // The traced outline itself, including the closing edge
+    for ((ax, ay), (bx, by)) in edges {
+        let steps = (bx - ax).abs().max((by - ay).abs()).max(1.);
+        for step in 0..=steps as usize {
+            let t = step as f64 / steps;
+            let x = (ax + (bx - ax) * t).round() as usize;
+            let y = (ay + (by - ay) * t).round() as usize;
+            if let Some(cell) = cells.get_mut(y).and_then(|row| row.get_mut(x)) {
+                *cell = true;
+            }
+        }
+    }
+
+    cells
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{draw_on_map, is_transparent, Locks, MapSize, TRANSPARENT};
+
+/// A small reusable grid placed onto the map. `TRANSPARENT` cells leave the
+/// map untouched.
+pub struct Stamp {
+    pub name: String,
+    pub cells: Vec<Vec<f64>>,
+}
+
+impl Stamp {
+    /// `#` is land, `.` is water and a space is transparent.
+    fn from_art(name: &str, art: &[&str]) -> Self {
+        let cells = art
+            .iter()
+            .map(|row| {
+                row.chars()
+                    .map(|ch| match ch {
+                        '#' => 1.,
+                        '.' => 0.,
+                        _ => TRANSPARENT,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Stamp {
+            name: name.to_string(),
+            cells,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.cells.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+/// On-disk form of a stamp. JSON has no NaN, so transparent cells are `null`.
+#[derive(Serialize, Deserialize)]
+struct StampFile {
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<Option<f64>>>,
+}
+
+/// Copies the `width`×`height` region at `(x, y)`, clipped to the map.
+pub fn copy_region(
+    map: &[Vec<f64>],
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+) -> Vec<Vec<f64>> {
+    map.iter()
+        .skip(y)
+        .take(height)
+        .map(|row| row.iter().skip(x).take(width).copied().collect())
+        .collect()
+}
+
+pub fn save_stamp(region: &[Vec<f64>], path: &Path) -> io::Result<()> {
+    let file = StampFile {
+        width: region.first().map_or(0, Vec::len),
+        height: region.len(),
+        cells: region
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&value| (!is_transparent(value)).then_some(value))
+                    .collect()
+            })
+            .collect(),
+    };
+
+    let content = serde_json::to_string(&file)?;
+    fs::write(path, content)
+}
+
+/// Saves `region` as the first free `stamp_<n>.json` in `dir`.
+pub fn save_stamp_in_dir(region: &[Vec<f64>], dir: &Path) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = (1..)
+        .map(|n| dir.join(format!("stamp_{}.json", n)))
+        .find(|path| !path.exists())
+        .unwrap();
+    save_stamp(region, &path)?;
+    Ok(path)
+}
+
+/// The stamp is named after the file stem.
+pub fn load_stamp(path: &Path) -> io::Result<Stamp> {
+    let content = fs::read_to_string(path)?;
+    let file: StampFile = serde_json::from_str(&content)?;
+
+    if file.cells.len() != file.height || file.cells.iter().any(|row| row.len() != file.width) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "stamp {} does not match its {}x{} size",
+                path.display(),
+                file.width,
+                file.height
+            ),
+        ));
+    }
+
+    Ok(Stamp {
+        name: path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned()),
+        cells: file
+            .cells
+            .into_iter()
+            .map(|row| row.into_iter().map(|cell| cell.unwrap_or(TRANSPARENT)).collect())
+            .collect(),
+    })
+}
+
+/// Loads every `*.json` stamp in `dir`, skipping (and logging) broken ones.
+pub fn load_stamp_dir(dir: &Path) -> Vec<Stamp> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    paths
+        .iter()
+        .filter_map(|path| match load_stamp(path) {
+            Ok(stamp) => Some(stamp),
+            Err(err) => {
+                log::warn!("failed to load stamp {}: {}", path.display(), err);
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn builtin_stamps() -> Vec<Stamp> {
+    vec![
+        Stamp::from_art(
+            "tree cluster",
+            &[
+                "  #  ",
+                " ### ",
+                "## ##",
+                " ### ",
+                "  #  ",
+            ],
+        ),
+        Stamp::from_art(
+            "house",
+            &[
+                "#######",
+                "#.....#",
+                "#.....#",
+                "#.....#",
+                "###.###",
+            ],
+        ),
+        Stamp::from_art(
+            "lake",
+            &[
+                "   ...   ",
+                " ....... ",
+                ".........",
+                ".........",
+                " ....... ",
+                "   ...   ",
+            ],
+        ),
+    ]
+}
+
+/// Places `stamp` centered on the map cell `(x, y)`, clipping at the edges.
+/// Locked cells are left alone. Returns `false` when part of it fell off the
+/// map.
+pub fn place_stamp(
+    map: &mut [Vec<f64>],
+    locked: &Locks,
+    stamp: &Stamp,
+    x: usize,
+    y: usize,
+) -> bool {
+    let origin_x = x as isize - (stamp.width() / 2) as isize;
+    let origin_y = y as isize - (stamp.height() / 2) as isize;
+    let size = MapSize::of(map);
+    let mut clipped = false;
+
+    for (dy, row) in stamp.cells.iter().enumerate() {
+        for (dx, &value) in row.iter().enumerate() {
+            let map_x = origin_x + dx as isize;
+            let map_y = origin_y + dy as isize;
+            if is_transparent(value) {
+                continue;
+            }
+            if map_x < 0 || map_y < 0 {
+                clipped = true;
+                continue;
+            }
+            let (map_x, map_y) = (map_x as usize, map_y as usize);
+            if draw_on_map(map, locked, map_x, map_y, value).is_none() {
+                clipped |= !size.contains(map_x, map_y);
+            }
+        }
+    }
+
+    !clipped
+}
@@ -0,0 +1,144 @@
+use ratatui::style::{Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph};
+
+use crate::prefs::Tiers;
+use crate::theme::Theme;
+use crate::{is_wall, tier_char};
+use crate::{EMPTY, FILLED, MISSING, MOUNTAIN, OUT_OF_RANGE, SAND, SHALLOW_WATER, SNOW};
+
+/// Tier names, each with the glyph `tier_char` draws its cells with.
+const TIERS: [(&str, char); 8] = [
+    ("deep water", EMPTY),
+    ("shallow water", SHALLOW_WATER),
+    ("sand", SAND),
+    ("grass", FILLED),
+    ("mountain", MOUNTAIN),
+    ("snow", SNOW),
+    ("missing", MISSING),
+    ("out of range", OUT_OF_RANGE),
+];
+
+/// `TIERS` starts with this many of water, then land up to `TERRAIN_TIERS`.
+const WATER_TIERS: usize = 2;
+const TERRAIN_TIERS: usize = 6;
+
+/// How many cells of a map fall in each height tier.
+pub struct MapStats {
+    /// Cells per tier, in the order of `TIERS`.
+    pub tiers: [usize; TIERS.len()],
+    pub walls: usize,
+    pub total: usize,
+}
+
+impl MapStats {
+    /// Deep and shallow water together.
+    pub fn water(&self) -> usize {
+        self.tiers[..WATER_TIERS].iter().sum()
+    }
+
+    /// Sand up to snow.
+    pub fn land(&self) -> usize {
+        self.tiers[WATER_TIERS..TERRAIN_TIERS].iter().sum()
+    }
+
+    fn percent(&self, count: usize) -> f64 {
+        if self.total == 0 {
+            0.
+        } else {
+            count as f64 * 100. / self.total as f64
+        }
+    }
+}
+
+/// Counts every cell of `map` into the tiers the elevation view draws, with
+/// walls counted on their own.
+pub fn map_statistics(map: &[Vec<f64>], water_level: f64, tiers: &Tiers) -> MapStats {
+    let mut stats = MapStats {
+        tiers: [0; TIERS.len()],
+        walls: 0,
+        total: 0,
+    };
+    for &value in map.iter().flatten() {
+        stats.total += 1;
+        if is_wall(value) {
+            stats.walls += 1;
+            continue;
+        }
+        let glyph = tier_char(value, water_level, tiers);
+        if let Some(tier) = TIERS.iter().position(|&(_, tier_glyph)| tier_glyph == glyph) {
+            stats.tiers[tier] += 1;
+        }
+    }
+    stats
+}
+
+/// One row per tier with its count and share of the map, then the water and
+/// land totals. Tiers no cell is in are left out past snow.
+pub fn paragraph(stats: &MapStats, theme: &Theme) -> Paragraph<'static> {
+    let row = |name: &str, count: usize| {
+        Line::from(format!("  {:<14}{:>8} {:>6.1}%", name, count, stats.percent(count)))
+    };
+    let mut lines: Vec<Line> = TIERS
+        .iter()
+        .zip(stats.tiers)
+        .enumerate()
+        .filter(|&(tier, (_, count))| tier < TERRAIN_TIERS || count > 0)
+        .map(|(_, (&(name, _), count))| row(name, count))
+        .collect();
+    if stats.walls > 0 {
+        lines.push(row("walls", stats.walls));
+    }
+    lines.push(Line::from(""));
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    lines.push(row("water", stats.water()).patch_style(bold));
+    lines.push(row("land", stats.land()).patch_style(bold));
+    lines.push(Line::from(Span::raw(format!("  {} cells", stats.total))));
+
+    Paragraph::new(lines).block(
+        Block::default()
+            .title("Statistics")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.popup)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TRANSPARENT, VALUE_LIMIT, WALL};
+
+    #[test]
+    fn every_cell_lands_in_one_tier() {
+        let tiers = Tiers::default();
+        let water_level = 0.;
+        let map = vec![
+            vec![-0.5, -0.1, 0.05, 0.3, 0.6],
+            vec![0.9, TRANSPARENT, VALUE_LIMIT + 1., WALL, 0.2],
+        ];
+        let stats = map_statistics(&map, water_level, &tiers);
+
+        assert_eq!(stats.tiers, [1, 1, 1, 2, 1, 1, 1, 1]);
+        assert_eq!(stats.walls, 1);
+        assert_eq!(stats.total, 10);
+        assert_eq!(stats.water(), 2);
+        assert_eq!(stats.land(), 5);
+        assert_eq!(stats.percent(stats.land()), 50.);
+    }
+
+    #[test]
+    fn raising_the_water_floods_land() {
+        let map = vec![vec![0.1, 0.2, 0.3, 0.4]];
+        let low = map_statistics(&map, 0., &Tiers::default());
+        let high = map_statistics(&map, 0.25, &Tiers::default());
+        assert_eq!((low.water(), low.land()), (0, 4));
+        assert_eq!((high.water(), high.land()), (2, 2));
+    }
+
+    #[test]
+    fn empty_map_has_no_shares() {
+        let stats = map_statistics(&[], 0., &Tiers::default());
+        assert_eq!(stats.total, 0);
+        assert_eq!(stats.percent(0), 0.);
+    }
+}
@@ -0,0 +1,523 @@
+use noise::{NoiseFn, Perlin};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::save;
+use crate::{is_wall, MapSize, WATER_LEVEL};
+
+/// Noise settings a map was generated with, kept so parts of it can be
+/// regenerated to match.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct NoiseParams {
+    #[serde(default)]
+    pub seed: u32,
+    /// Noise periods across the whole map.
+    pub frequency: f64,
+    /// Shift in noise space; a new offset rerolls the terrain without
+    /// changing its scale.
+    #[serde(default)]
+    pub offset: (f64, f64),
+    /// Layers of noise summed together, each at twice the frequency of the
+    /// one before. One layer is the plain Perlin look.
+    #[serde(default = "default_octaves")]
+    pub octaves: u32,
+    /// How much of the previous layer's amplitude each layer keeps.
+    #[serde(default = "default_persistence")]
+    pub persistence: f64,
+    /// Multiplies the noise towards 0 away from the centre, for an island.
+    #[serde(default)]
+    pub island: Option<IslandMask>,
+}
+
+fn default_octaves() -> u32 {
+    1
+}
+
+fn default_persistence() -> f64 {
+    0.5
+}
+
+impl NoiseParams {
+    pub fn new(seed: u32) -> Self {
+        NoiseParams {
+            seed,
+            frequency: 10.,
+            offset: (0., 0.),
+            octaves: default_octaves(),
+            persistence: default_persistence(),
+            island: None,
+        }
+    }
+
+    /// Noise at `(nx, ny)`, with both running 0 to 1 across the map. The
+    /// octaves are divided by their total amplitude so the range stays that
+    /// of a single one.
+    fn sample(&self, perlin: &Perlin, nx: f64, ny: f64) -> f64 {
+        let (mut sum, mut total) = (0., 0.);
+        let (mut frequency, mut amplitude) = (self.frequency, 1.);
+        for _ in 0..self.octaves.max(1) {
+            let point = [nx * frequency + self.offset.0, ny * frequency + self.offset.1];
+            sum += perlin.get(point) * amplitude;
+            total += amplitude;
+            frequency *= 2.;
+            amplitude *= self.persistence;
+        }
+        sum / total
+    }
+
+    /// Same seed and frequency at a random offset.
+    pub fn reroll(&self) -> Self {
+        NoiseParams {
+            offset: (rand::random::<f64>() * 1000., rand::random::<f64>() * 1000.),
+            ..*self
+        }
+    }
+}
+
+/// How `IslandMask` falls from the centre of the map to its edges.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Falloff {
+    /// Evenly all the way out.
+    Linear,
+    /// Slowly near the centre and steeply at the coast.
+    Squared,
+}
+
+impl Falloff {
+    pub fn parse(name: &str) -> Option<Falloff> {
+        match name {
+            "linear" => Some(Falloff::Linear),
+            "squared" => Some(Falloff::Squared),
+            _ => None,
+        }
+    }
+}
+
+/// Radial gradient, 1 at the centre and `1 - strength` from the middle of
+/// each edge outwards, so noise multiplied by it sinks into the sea there.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct IslandMask {
+    pub strength: f64,
+    pub falloff: Falloff,
+}
+
+impl IslandMask {
+    /// The gradient at `(nx, ny)`, with both running 0 to 1 across the map.
+    fn factor(&self, nx: f64, ny: f64) -> f64 {
+        let (dx, dy) = (nx * 2. - 1., ny * 2. - 1.);
+        let distance = (dx * dx + dy * dy).sqrt().min(1.);
+        let drop = match self.falloff {
+            Falloff::Linear => distance,
+            Falloff::Squared => distance * distance,
+        };
+        1. - self.strength * drop
+    }
+}
+
+/// Multiplies the cells from `(x0, y0)` up to, but not including, `(x1, y1)`
+/// by `mask`, measured across the whole map.
+pub fn apply_island_mask(
+    map: &mut [Vec<f64>],
+    (x0, y0, x1, y1): (usize, usize, usize, usize),
+    mask: &IslandMask,
+) {
+    let size = MapSize::of(map);
+    // The outermost cells sit on the edge, so a full mask reaches 0 there
+    let span = |len: usize| (len.max(2) - 1) as f64;
+    let (width, height) = (span(size.width), span(size.height));
+    for (y, row) in map.iter_mut().enumerate().take(y1).skip(y0) {
+        for (x, cell) in row.iter_mut().enumerate().take(x1).skip(x0) {
+            *cell *= mask.factor(x as f64 / width, y as f64 / height);
+        }
+    }
+}
+
+/// Writes fresh noise into the cells from `(x0, y0)` up to, but not
+/// including, `(x1, y1)`, clipped to the map.
+pub fn regen_region(
+    map: &mut [Vec<f64>],
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+    params: &NoiseParams,
+) {
+    let perlin = Perlin::new(params.seed);
+    let size = MapSize::of(map);
+
+    for (y, row) in map.iter_mut().enumerate().take(y1).skip(y0) {
+        for (x, cell) in row.iter_mut().enumerate().take(x1).skip(x0) {
+            let nx = x as f64 / size.width as f64;
+            let ny = y as f64 / size.height as f64;
+            *cell = params.sample(&perlin, nx, ny);
+        }
+    }
+    if let Some(mask) = &params.island {
+        apply_island_mask(map, (x0, y0, x1, y1), mask);
+    }
+}
+
+/// Steps a droplet takes before it has evaporated.
+const DROPLET_LIFETIME: usize = 30;
+/// How much of its direction a droplet keeps instead of following the slope.
+const INERTIA: f64 = 0.05;
+const SEDIMENT_CAPACITY: f64 = 1.;
+/// Keeps droplets carrying a little on flat ground.
+const MIN_CAPACITY: f64 = 0.001;
+const ERODE_SPEED: f64 = 0.1;
+const DEPOSIT_SPEED: f64 = 0.3;
+const EVAPORATE_SPEED: f64 = 0.02;
+const GRAVITY: f64 = 4.;
+
+/// Hydraulic erosion: `iterations` passes of `droplets` raindrops each, rolling
+/// downhill, picking up height on steep slopes and dropping it where they
+/// slow down. Droplets stop at walls, missing cells and the map edge, which
+/// are never changed. `on_iteration` runs after each pass.
+pub fn erode(
+    map: &mut [Vec<f64>],
+    droplets: usize,
+    iterations: usize,
+    seed: u32,
+    mut on_iteration: impl FnMut(),
+) {
+    let size = MapSize::of(map);
+    // Droplets need a cell to each side to roll between
+    if size.width < 2 || size.height < 2 {
+        return;
+    }
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    for _ in 0..iterations {
+        for _ in 0..droplets {
+            let x = rng.gen_range(0. ..(size.width - 1) as f64);
+            let y = rng.gen_range(0. ..(size.height - 1) as f64);
+            roll_droplet(map, x, y);
+        }
+        on_iteration();
+    }
+}
+
+fn roll_droplet(map: &mut [Vec<f64>], mut x: f64, mut y: f64) {
+    let (mut dx, mut dy) = (0., 0.);
+    let (mut speed, mut water, mut sediment) = (1., 1., 0.);
+
+    for _ in 0..DROPLET_LIFETIME {
+        let Some((height, gradient_x, gradient_y)) = surface(map, x, y) else {
+            return;
+        };
+        dx = dx * INERTIA - gradient_x * (1. - INERTIA);
+        dy = dy * INERTIA - gradient_y * (1. - INERTIA);
+        let length = f64::hypot(dx, dy);
+        if length == 0. {
+            return;
+        }
+        (dx, dy) = (dx / length, dy / length);
+
+        let (old_x, old_y) = (x, y);
+        (x, y) = (x + dx, y + dy);
+        let Some((new_height, _, _)) = surface(map, x, y) else {
+            return;
+        };
+        let climb = new_height - height;
+
+        let capacity = (-climb * speed * water * SEDIMENT_CAPACITY).max(MIN_CAPACITY);
+        if climb > 0. || sediment > capacity {
+            // Uphill fills the pit behind it, at most up to the next cell
+            let amount = if climb > 0. {
+                climb.min(sediment)
+            } else {
+                (sediment - capacity) * DEPOSIT_SPEED
+            };
+            spread(map, old_x, old_y, amount);
+            sediment -= amount;
+        } else {
+            let amount = ((capacity - sediment) * ERODE_SPEED).min(-climb);
+            spread(map, old_x, old_y, -amount);
+            sediment += amount;
+        }
+
+        speed = (speed * speed - climb * GRAVITY).max(0.).sqrt();
+        water *= 1. - EVAPORATE_SPEED;
+    }
+}
+
+/// Height and slope at a point between cell centres, blended from the four
+/// cells around it. `None` if any of them is off the map or not terrain.
+fn surface(map: &[Vec<f64>], x: f64, y: f64) -> Option<(f64, f64, f64)> {
+    let (cell_x, cell_y) = (x.floor() as usize, y.floor() as usize);
+    let size = MapSize::of(map);
+    if x < 0. || y < 0. || cell_x + 1 >= size.width || cell_y + 1 >= size.height {
+        return None;
+    }
+    let corner = |dx: usize, dy: usize| {
+        let value = map[cell_y + dy][cell_x + dx];
+        passable(value).then_some(value)
+    };
+    let (nw, ne) = (corner(0, 0)?, corner(1, 0)?);
+    let (sw, se) = (corner(0, 1)?, corner(1, 1)?);
+    let (u, v) = (x.fract(), y.fract());
+
+    let gradient_x = (ne - nw) * (1. - v) + (se - sw) * v;
+    let gradient_y = (sw - nw) * (1. - u) + (se - ne) * u;
+    let height = nw * (1. - u) * (1. - v) + ne * u * (1. - v) + sw * (1. - u) * v + se * u * v;
+    Some((height, gradient_x, gradient_y))
+}
+
+/// Adds `amount` to the four cells around a point, weighted by closeness.
+fn spread(map: &mut [Vec<f64>], x: f64, y: f64, amount: f64) {
+    let (cell_x, cell_y) = (x.floor() as usize, y.floor() as usize);
+    let (u, v) = (x.fract(), y.fract());
+    map[cell_y][cell_x] += amount * (1. - u) * (1. - v);
+    map[cell_y][cell_x + 1] += amount * u * (1. - v);
+    map[cell_y + 1][cell_x] += amount * (1. - u) * v;
+    map[cell_y + 1][cell_x + 1] += amount * u * v;
+}
+
+/// Rivers start at least this many cells apart, so they don't all share one
+/// peak.
+const SOURCE_SPACING: usize = 16;
+/// How far below the water level river beds are carved.
+const RIVER_DEPTH: f64 = 0.05;
+
+/// Traces up to `count` rivers from the highest land down to the water and
+/// carves each into a channel below `water_level`. A river ends where it
+/// reaches water, the map edge or an earlier river, which it then joins.
+/// Returns how many rivers were traced.
+pub fn generate_rivers(map: &mut [Vec<f64>], count: usize, water_level: f64) -> usize {
+    let size = MapSize::of(map);
+    let mut land: Vec<(usize, usize)> = (0..size.height)
+        .flat_map(|y| (0..size.width).map(move |x| (x, y)))
+        .filter(|&(x, y)| map[y][x] > water_level && passable(map[y][x]))
+        .collect();
+    land.sort_by(|&(ax, ay), &(bx, by)| map[by][bx].total_cmp(&map[ay][ax]));
+
+    let mut sources: Vec<(usize, usize)> = Vec::new();
+    for (x, y) in land {
+        if sources.len() == count {
+            break;
+        }
+        let spaced = sources.iter().all(|&(source_x, source_y)| {
+            source_x.abs_diff(x).max(source_y.abs_diff(y)) >= SOURCE_SPACING
+        });
+        if spaced {
+            sources.push((x, y));
+        }
+    }
+
+    // Trace every river over the uncarved heights, then carve them together
+    let mut river = size.grid(false);
+    for &source in &sources {
+        trace_river(map, &mut river, source, water_level);
+    }
+    for (row, river) in map.iter_mut().zip(&river) {
+        for (cell, _) in row.iter_mut().zip(river).filter(|(_, &river)| river) {
+            *cell = cell.min(water_level - RIVER_DEPTH);
+        }
+    }
+
+    sources.len()
+}
+
+fn passable(value: f64) -> bool {
+    !value.is_nan() && !is_wall(value)
+}
+
+/// Follows the lowest neighbour from `source`, adding the path to `river`.
+/// Flats and pits are crossed by taking the lowest cell not yet on this
+/// path, so every walk ends.
+fn trace_river(
+    map: &[Vec<f64>],
+    river: &mut [Vec<bool>],
+    source: (usize, usize),
+    water_level: f64,
+) {
+    let size = MapSize::of(map);
+    let mut path = size.grid(false);
+    let (mut x, mut y) = source;
+
+    while !river[y][x] {
+        path[y][x] = true;
+        let at_edge = x == 0 || y == 0 || x == size.width - 1 || y == size.height - 1;
+        if map[y][x] <= water_level || at_edge {
+            break;
+        }
+
+        let mut lowest: Option<(usize, usize)> = None;
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let (Some(nx), Some(ny)) = (x.checked_add_signed(dx), y.checked_add_signed(dy))
+                else {
+                    continue;
+                };
+                if path[ny][nx] || !passable(map[ny][nx]) {
+                    continue;
+                }
+                if lowest.map_or(true, |(lx, ly)| map[ny][nx] < map[ly][lx]) {
+                    lowest = Some((nx, ny));
+                }
+            }
+        }
+        match lowest {
+            Some(next) => (x, y) = next,
+            None => break,
+        }
+    }
+
+    for (row, path) in river.iter_mut().zip(&path) {
+        for (cell, _) in row.iter_mut().zip(path).filter(|(_, &on_path)| on_path) {
+            *cell = true;
+        }
+    }
+}
+
+/// Generator for the map the editor opens with.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum StartMap {
+    Empty,
+    Noise,
+    /// Noise fading to water towards the edges.
+    Island,
+    /// Cellular automaton caves, rock as land and open floor as water.
+    Caves,
+}
+
+impl StartMap {
+    pub fn parse(name: &str) -> Option<StartMap> {
+        match name {
+            "empty" => Some(StartMap::Empty),
+            "noise" => Some(StartMap::Noise),
+            "island" => Some(StartMap::Island),
+            "caves" => Some(StartMap::Caves),
+            _ => None,
+        }
+    }
+}
+
+/// Added to the noise before the island mask so most of the middle is land,
+/// and `ISLAND_SINK` taken off after it so the faded edges are sea.
+const ISLAND_LIFT: f64 = 0.6;
+const ISLAND_SINK: f64 = 0.3;
+
+/// Noise with the island mask, a full linear one unless `params` has its
+/// own, so land gathers in the middle.
+pub fn island_map(params: &NoiseParams, size: MapSize) -> Vec<Vec<f64>> {
+    let mask = params.island.unwrap_or(IslandMask {
+        strength: 1.,
+        falloff: Falloff::Linear,
+    });
+    let params = NoiseParams {
+        island: None,
+        ..*params
+    };
+    let mut map = size.grid(0.);
+    regen_region(&mut map, 0, 0, size.width, size.height, &params);
+
+    map.iter_mut().flatten().for_each(|cell| *cell += ISLAND_LIFT);
+    apply_island_mask(&mut map, (0, 0, size.width, size.height), &mask);
+    map.iter_mut().flatten().for_each(|cell| *cell -= ISLAND_SINK);
+
+    map
+}
+
+/// Random rock smoothed by the usual 4-5 rule: a cell becomes rock with more
+/// than four rock neighbours and floor with fewer than four. The map border
+/// counts as rock.
+pub fn caves_map(seed: u32, size: MapSize) -> Vec<Vec<f64>> {
+    const FILL_CHANCE: f64 = 0.45;
+    const STEPS: usize = 5;
+
+    let mut rng = StdRng::seed_from_u64(seed as u64);
+    let mut map: Vec<Vec<f64>> = (0..size.height)
+        .map(|_| {
+            (0..size.width)
+                .map(|_| if rng.gen_bool(FILL_CHANCE) { 1. } else { 0. })
+                .collect()
+        })
+        .collect();
+
+    for _ in 0..STEPS {
+        let previous = map.clone();
+        for (y, row) in map.iter_mut().enumerate() {
+            for (x, cell) in row.iter_mut().enumerate() {
+                match rock_neighbours(&previous, x, y).cmp(&4) {
+                    Ordering::Greater => *cell = 1.,
+                    Ordering::Less => *cell = 0.,
+                    Ordering::Equal => {}
+                }
+            }
+        }
+    }
+
+    map
+}
+
+fn rock_neighbours(map: &[Vec<f64>], x: usize, y: usize) -> usize {
+    let mut count = 0;
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            if (dx, dy) == (0, 0) {
+                continue;
+            }
+            let rock = match (x.checked_add_signed(dx), y.checked_add_signed(dy)) {
+                (Some(nx), Some(ny)) if MapSize::of(map).contains(nx, ny) => map[ny][nx] > 0.,
+                _ => true,
+            };
+            count += rock as usize;
+        }
+    }
+    count
+}
+
+/// Colors used by the half-block renderer.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Palette {
+    pub land: Color,
+    pub water: Color,
+}
+
+/// A named map style: how to generate it and how to draw it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    /// Applying a preset keeps the current seed, and the current island mask
+    /// when this has none; every other field is taken from here.
+    pub noise: NoiseParams,
+    pub water_level: f64,
+    pub palette: Palette,
+}
+
+impl Preset {
+    fn builtin(name: &str, frequency: f64, water_level: f64, land: Color, water: Color) -> Self {
+        Preset {
+            name: name.to_string(),
+            noise: NoiseParams {
+                frequency,
+                ..NoiseParams::new(0)
+            },
+            water_level,
+            palette: Palette { land, water },
+        }
+    }
+}
+
+/// The first entry matches the look the editor always had.
+pub fn builtin_presets() -> Vec<Preset> {
+    vec![
+        Preset::builtin("default", 10., WATER_LEVEL, Color::Green, Color::Blue),
+        Preset::builtin("archipelago", 16., 0.25, Color::LightGreen, Color::Cyan),
+        Preset::builtin("continents", 4., -0.1, Color::Green, Color::Blue),
+        Preset::builtin("mountains", 8., -0.4, Color::Gray, Color::Blue),
+    ]
+}
+
+/// Reads a JSON or RON list of presets, by extension.
+pub fn load_presets(path: &Path) -> io::Result<Vec<Preset>> {
+    let content = fs::read_to_string(path)?;
+    save::decode(&content, path)
+}
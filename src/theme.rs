@@ -0,0 +1,101 @@
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::save;
+use crate::terrain::Palette;
+
+/// Colors for everything that isn't map data. Colors are given by name or
+/// as `#rrggbb`; fields left out of a theme file keep the default.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub name: String,
+    /// Replaces the land and water colors of every preset when set.
+    pub terrain: Option<Palette>,
+    pub wall: Color,
+    /// Missing and out-of-range cells.
+    pub invalid: Color,
+    /// Note and pin glyphs.
+    pub marker: Color,
+    pub ruler: Color,
+    /// Status bar, drawn reversed.
+    pub status: Color,
+    /// Help popup border.
+    pub help: Color,
+    /// Prompt, number input and file browser borders.
+    pub popup: Color,
+    pub inspector: Color,
+    pub hud: Color,
+    /// Cells higher than in the `:diff` comparison.
+    pub diff_added: Color,
+    /// Cells lower than in the `:diff` comparison.
+    pub diff_removed: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            name: "default".to_string(),
+            terrain: None,
+            wall: Color::Gray,
+            invalid: Color::Magenta,
+            marker: Color::White,
+            ruler: Color::Reset,
+            status: Color::Reset,
+            help: Color::Yellow,
+            popup: Color::Yellow,
+            inspector: Color::Cyan,
+            hud: Color::Magenta,
+            diff_added: Color::Green,
+            diff_removed: Color::Red,
+        }
+    }
+}
+
+impl Theme {
+    pub fn status_style(&self) -> Style {
+        Style::default().fg(self.status).add_modifier(Modifier::REVERSED)
+    }
+
+    /// Land and water for `palette`'s preset under this theme.
+    pub fn palette(&self, palette: Palette) -> Palette {
+        self.terrain.unwrap_or(palette)
+    }
+}
+
+/// The default theme first, then ones for light terminals and for telling
+/// land from water without red or green.
+pub fn builtin_themes() -> Vec<Theme> {
+    vec![
+        Theme::default(),
+        Theme {
+            name: "light".to_string(),
+            wall: Color::DarkGray,
+            marker: Color::Black,
+            help: Color::Blue,
+            popup: Color::Blue,
+            inspector: Color::Blue,
+            ..Theme::default()
+        },
+        Theme {
+            name: "colorblind".to_string(),
+            terrain: Some(Palette {
+                land: Color::Yellow,
+                water: Color::Blue,
+            }),
+            invalid: Color::White,
+            diff_added: Color::Yellow,
+            diff_removed: Color::Blue,
+            ..Theme::default()
+        },
+    ]
+}
+
+/// Reads a JSON or RON list of themes, by extension.
+pub fn load_themes(path: &Path) -> io::Result<Vec<Theme>> {
+    let content = fs::read_to_string(path)?;
+    save::decode(&content, path)
+}
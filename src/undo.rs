@@ -0,0 +1,85 @@
+/// Oldest edits are dropped past this many.
+const MAX_EDITS: usize = 500;
+
+/// One cell an edit wrote: where, what it held and what it got.
+#[derive(Clone, Copy)]
+struct Change {
+    x: usize,
+    y: usize,
+    before: f64,
+    after: f64,
+}
+
+/// Map edits for Ctrl+z and Ctrl+y. Changes are found by comparing the map
+/// with a copy taken after the previous edit, so painting, fills, commands
+/// and finished tasks are all covered without reporting what they wrote.
+pub struct UndoStack {
+    done: Vec<Vec<Change>>,
+    undone: Vec<Vec<Change>>,
+    last: Vec<Vec<f64>>,
+}
+
+impl UndoStack {
+    pub fn new(map: &[Vec<f64>]) -> Self {
+        UndoStack {
+            done: Vec::new(),
+            undone: Vec::new(),
+            last: map.to_vec(),
+        }
+    }
+
+    /// Ends an edit: every cell changed since the previous one becomes a
+    /// single step. Starting a new edit forgets what was undone.
+    pub fn record(&mut self, map: &[Vec<f64>]) {
+        let mut changes = Vec::new();
+        for (y, (row, last)) in map.iter().zip(&mut self.last).enumerate() {
+            for (x, (&after, before)) in row.iter().zip(last.iter_mut()).enumerate() {
+                // Bit for bit, so holes compare equal
+                if after.to_bits() != before.to_bits() {
+                    changes.push(Change { x, y, before: *before, after });
+                    *before = after;
+                }
+            }
+        }
+        if changes.is_empty() {
+            return;
+        }
+        self.done.push(changes);
+        if self.done.len() > MAX_EDITS {
+            self.done.remove(0);
+        }
+        self.undone.clear();
+    }
+
+    /// Takes the map as it is without an undo step, for changes that are
+    /// not edits such as the running simulation.
+    pub fn rebase(&mut self, map: &[Vec<f64>]) {
+        for (last, row) in self.last.iter_mut().zip(map) {
+            last.copy_from_slice(row);
+        }
+    }
+
+    /// Reverts the latest edit, returning how many cells it wrote.
+    pub fn undo(&mut self, map: &mut [Vec<f64>]) -> Option<usize> {
+        let edit = self.done.pop()?;
+        for change in &edit {
+            map[change.y][change.x] = change.before;
+            self.last[change.y][change.x] = change.before;
+        }
+        let count = edit.len();
+        self.undone.push(edit);
+        Some(count)
+    }
+
+    /// Applies the latest undone edit again.
+    pub fn redo(&mut self, map: &mut [Vec<f64>]) -> Option<usize> {
+        let edit = self.undone.pop()?;
+        for change in &edit {
+            map[change.y][change.x] = change.after;
+            self.last[change.y][change.x] = change.after;
+        }
+        let count = edit.len();
+        self.done.push(edit);
+        Some(count)
+    }
+}